@@ -8,10 +8,12 @@ use syn::token::Comma;
 use syn::{parse_quote, Arm};
 
 // #[glacier(GET, "/")]
+// #[glacier(POST, "/upload", [ip_middle(100, 5)], limit = "10MB")]
 struct RouteArgs {
     method: syn::Ident,
     path: syn::LitStr,
     middles: Option<syn::ExprArray>,
+    limit: Option<u64>,
 }
 
 impl Parse for RouteArgs {
@@ -20,29 +22,61 @@ impl Parse for RouteArgs {
         let _comma: Comma = input.parse()?;
         let path = input.parse()?;
 
-        let comma: Result<Comma, syn::Error> = input.parse();
-        if comma.is_err() {
-            Ok(RouteArgs {
-                method,
-                path,
-                middles: None,
-            })
-        } else {
-            let middles: syn::ExprArray = input.parse()?;
+        let mut middles = None;
+        let mut limit = None;
+        while input.parse::<Comma>().is_ok() {
+            if input.peek(syn::token::Bracket) {
+                middles = Some(input.parse()?);
+                continue;
+            }
 
-            Ok(RouteArgs {
-                method,
-                path,
-                middles: Some(middles),
-            })
+            let key: syn::Ident = input.parse()?;
+            let _eq: syn::Token![=] = input.parse()?;
+            match key.to_string().as_str() {
+                "limit" => {
+                    let value: syn::LitStr = input.parse()?;
+                    let bytes = parse_size(&value.value()).ok_or_else(|| {
+                        syn::Error::new(value.span(), "invalid size, e.g. \"10MB\"")
+                    })?;
+                    limit = Some(bytes);
+                }
+                _ => return Err(syn::Error::new(key.span(), "unknown argument")),
+            }
         }
+
+        Ok(RouteArgs {
+            method,
+            path,
+            middles,
+            limit,
+        })
     }
 }
 
+/// 解析 `"512"`、`"64KB"`、`"10MB"`、`"1GB"` 这样的大小
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let pos = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (num, unit) = size.split_at(pos);
+    let num: u64 = num.parse().ok()?;
+
+    let unit = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" | "K" => 1024,
+        "MB" | "M" => 1024 * 1024,
+        "GB" | "G" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    num.checked_mul(unit)
+}
+
 //////////////////////////////
 
 static mut ARMS: Vec<String> = Vec::new();
 static mut CONTAIN_PATH: Vec<String> = Vec::new();
+static mut ROUTE_LIMIT: Vec<String> = Vec::new();
 
 //////////////////////////////
 
@@ -66,6 +100,18 @@ fn gen_glacier(ast: syn::ItemFn, args: RouteArgs) -> TokenStream {
     let _method = args.method;
     let path = args.path;
     let middles = args.middles;
+    let limit = args.limit.map(|limit| -> syn::Stmt {
+        parse_quote! { req.limit_body(#limit).await?; }
+    });
+
+    /* ------------------------------ // route_limit 分支 ------------------------------ */
+    if let Some(limit) = args.limit {
+        let arm: syn::Arm = parse_quote! {
+            #path => Some(#limit),
+        };
+        let arm = arm.to_token_stream().to_string();
+        unsafe { ROUTE_LIMIT.push(arm) };
+    }
 
     /* ------------------------------ // contain_uri 分支 ------------------------------ */
     let arm: syn::Arm = parse_quote! {
@@ -92,12 +138,14 @@ fn gen_glacier(ast: syn::ItemFn, args: RouteArgs) -> TokenStream {
     let arm: syn::Arm = match middles {
         Some(middles) => parse_quote! {
             #path => {
+                #limit
                 # ( req = #middles.await?; )*
                 # (#func_body_stmts) *
             }
         },
         None => parse_quote! {
             #path => {
+                #limit
                 # (#func_body_stmts) *
             }
         },
@@ -158,6 +206,32 @@ fn gen_main(mut ast: syn::ItemFn) -> TokenStream {
         },
     );
 
+    /* ------------------------------ // route_limit 分支 ------------------------------ */
+    let arms = unsafe { ROUTE_LIMIT.clone() };
+    let mut arms: Vec<Arm> = arms
+        .into_iter()
+        .map(|stmt| syn::parse_str(&stmt).unwrap())
+        .collect();
+    arms.push(parse_quote! {
+        _ => None,
+    });
+    let mut match_expr: syn::ExprMatch = parse_quote! {
+        match path {}
+    };
+    match_expr.arms = arms;
+    let route_limit: syn::ItemFn = parse_quote! {
+        fn route_limit(path: &str) -> Option<u64> {
+            #match_expr
+        }
+    };
+
+    ast.block.stmts.insert(
+        1,
+        parse_quote! {
+            unsafe { ROUTE_LIMIT = route_limit; }
+        },
+    );
+
     /* ------------------------------ // 处理 match1 ------------------------------ */
     let arms = unsafe { ARMS.clone() };
     let mut arms: Vec<Arm> = arms
@@ -167,20 +241,7 @@ fn gen_main(mut ast: syn::ItemFn) -> TokenStream {
 
     let _arm = parse_quote! {
         _ => {
-            if unsafe { !DIR_PATH.is_empty() } {
-                let pos = req.path_for_routes().rfind("/").unwrap_or(0);
-                let req_dir_path = &req.path_for_routes()[..pos];
-                if req_dir_path != unsafe { DIR_PATH } {
-                    req.respond_404().await?;
-                    return Ok(req);
-                }
-
-                let file_path = String::from(&req.path_for_routes()[1..]);
-                if let Err(e) = req.respond_buf(file_path).await {
-                    req.respond_404().await?;
-                }
-            }
-
+            req.respond_static().await?;
         }
     };
     arms.push(_arm);
@@ -201,6 +262,7 @@ fn gen_main(mut ast: syn::ItemFn) -> TokenStream {
     let gen = quote! {
 
         #contain_path
+        #route_limit
         /// 由宏生成的函数, 每个请求都会进入这个函数, 通过`req.path()`进入不同的match分支
         /// # Examples
         /// ```
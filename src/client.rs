@@ -1,16 +1,23 @@
 use bytes::BytesMut;
-use std::{future::Future, net::IpAddr, time::Duration};
+use std::{future::Future, net::IpAddr, sync::Arc, time::Duration};
 use tokio::{
-    io::AsyncReadExt,
-    net::{TcpListener, TcpStream},
-    time::timeout,
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    time::{timeout, timeout_at, Instant},
 };
+#[cfg(feature = "tls")]
 use tokio_rustls::TlsAcceptor;
 
+use crate::config::{GlacierConfig, TrailingSlash};
+use crate::middles::canonical_middle::canonical_middle;
+use crate::stream::glacier_stream::GlacierStream;
 use crate::stream::request::{ReqInfo, RequestHeader, RequestLine};
 use crate::{
     error::Kind,
-    prelude::{GlacierError, OneRequest, Result, Routes},
+    prelude::{
+        GlacierError, OneRequest, Response, ResponseBuilder, Result, Routes, StatusCode,
+        CONTAIN_PATH, ROUTE_LIMIT,
+    },
 };
 
 //
@@ -25,12 +32,13 @@ use crate::{
 pub struct Glacier<T> {
     pub(crate) listener: TcpListener,
     pub(crate) routes: Routes<T>,
+    pub(crate) config: Arc<GlacierConfig>,
 }
 
 #[cfg(not(feature = "tls"))]
 impl<T> Glacier<T>
 where
-    T: Future<Output = Result<OneRequest>> + Send + Sync + 'static,
+    T: Future<Output = Result<OneRequest>> + Send + 'static,
 {
     /// 开始运行代码
     /// # Examples
@@ -41,13 +49,15 @@ where
     pub async fn run(self) -> Result<()> {
         let routes = self.routes;
         let listener = self.listener;
+        let config = self.config;
 
         let srv = async move {
             loop {
                 match listener.accept().await {
                     Ok((stream, addr)) => {
+                        let config = config.clone();
                         tokio::spawn(async move {
-                            Glacier::handle_connection(stream, routes, addr.ip())
+                            handle_connection(stream, routes, config, addr.ip())
                                 .await
                                 .unwrap();
                         });
@@ -61,105 +71,6 @@ where
 
         Ok(())
     }
-
-    async fn handle_connection(
-        mut stream: TcpStream,
-        routes: Routes<T>,
-        addr: IpAddr,
-    ) -> Result<()> {
-        tracing::info!("new connection!");
-        let mut buf = BytesMut::with_capacity(1024);
-        loop {
-            let req_info = match read_stream(&mut stream, &mut buf).await {
-                Ok(req_info) => req_info,
-                Err(e) => {
-                    match e {
-                        GlacierError::OkErr(_kind @ Kind::EofErr) => {}
-                        GlacierError::Option => {}
-                        _ => tracing::debug!("{:#?}", e),
-                    }
-                    return Ok(());
-                }
-            };
-            let mut one_req = OneRequest::new(stream, buf, req_info, addr);
-
-            one_req = match routes(one_req).await {
-                Ok(one_req) => one_req,
-                Err(_) => return Ok(()),
-            };
-            stream = one_req.stream;
-            buf = one_req.buf;
-            buf.clear();
-        }
-    }
-}
-
-#[cfg(not(feature = "tls"))]
-async fn read_stream(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<ReqInfo> {
-    /* --------------------------------- // 准备工作 -------------------------------- */
-    let mut pos = Vec::with_capacity(10);
-    pos.push(0);
-    buf.clear();
-
-    /* ------------------------ // 读取数据到buf, 然后标记buf上的位置 ------------------------ */
-    loop {
-        let read_task: _ = stream.read_buf(buf);
-        let read_task: _ = timeout(Duration::from_secs(10), read_task);
-
-        let len = match read_task.await {
-            Ok(Ok(0)) => Err(GlacierError::OkErr(Kind::EofErr))?,
-            Ok(Ok(len @ 1..)) => len,
-            Ok(Err(e)) => Err(e)?,
-            _ => Err(GlacierError::OkErr(Kind::TimeOutErr))?,
-        };
-
-        for i in (buf.len() - len..buf.len()).step_by(2) {
-            match buf[i] {
-                b'\r' => {
-                    if let Some(_x @ b'\n') = buf.get(i + 1) {
-                        pos.push(i + 2);
-                    }
-                }
-                b'\n' => {
-                    if buf[i - 1] == b'\r' {
-                        pos.push(i + 1);
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        // 判断是否到了 请求体
-        let len = pos.len();
-        if len >= 2 && pos[len - 1] - pos[len - 2] == 2 {
-            pos.pop();
-            break;
-        }
-    }
-
-    let mut lines = pos
-        .as_slice()
-        .iter()
-        .enumerate()
-        .skip(1)
-        .map(|(i, pos_temp)| [pos[i - 1], *pos_temp]);
-
-    // 请求行处理
-    let request_line = lines.next().ok_or_else(|| {
-        tracing::debug!("lines is empty");
-        GlacierError::Option
-    })?;
-    let line_pos = RequestLine::parse(&buf, request_line)?;
-
-    // 请求头处理
-    let headers_pos: Vec<[usize; 3]> = lines
-        .map(|line| RequestHeader::parse(&buf, line))
-        .collect::<Result<Vec<_>>>()?;
-
-    Ok(ReqInfo {
-        line_pos,
-        headers_pos,
-    })
 }
 
 #[cfg(feature = "tls")]
@@ -167,12 +78,13 @@ pub struct Glacier<T> {
     pub(crate) listener: TcpListener,
     pub(crate) routes: Routes<T>,
     pub(crate) acceptor: TlsAcceptor,
+    pub(crate) config: Arc<GlacierConfig>,
 }
 
 #[cfg(feature = "tls")]
 impl<T> Glacier<T>
 where
-    T: Future<Output = Result<OneRequest>> + Send + Sync + 'static,
+    T: Future<Output = Result<OneRequest>> + Send + 'static,
 {
     /// 开始运行代码
     /// # Examples
@@ -183,6 +95,7 @@ where
     pub async fn run(self) -> Result<()> {
         let routes = self.routes;
         let listener = self.listener;
+        let config = self.config;
         let acceptor = self.acceptor;
 
         let srv = async move {
@@ -195,8 +108,9 @@ where
                             Err(_) => continue,
                         };
 
+                        let config = config.clone();
                         tokio::spawn(async move {
-                            Glacier::handle_connection(stream, routes, addr.ip())
+                            handle_connection(stream, routes, config, addr.ip())
                                 .await
                                 .unwrap();
                         });
@@ -210,85 +124,258 @@ where
 
         Ok(())
     }
+}
+
+/// 请求头的最大长度
+const MAX_HEAD_LEN: usize = 64 * 1024;
 
-    async fn handle_connection(
-        mut stream: tokio_rustls::server::TlsStream<TcpStream>,
-        routes: Routes<T>,
-        addr: IpAddr,
-    ) -> Result<()> {
-        tracing::info!("new connection!");
-        let mut buf = BytesMut::with_capacity(1024);
-        loop {
-            let req_info = match read_stream(&mut stream, &mut buf).await {
-                Ok(req_info) => req_info,
-                Err(e) => {
-                    match e {
-                        GlacierError::OkErr(_kind @ Kind::EofErr) => {}
-                        GlacierError::Option => {}
-                        _ => tracing::debug!("{:#?}", e),
+async fn handle_connection<T>(
+    mut stream: GlacierStream,
+    routes: Routes<T>,
+    config: Arc<GlacierConfig>,
+    addr: IpAddr,
+) -> Result<()>
+where
+    T: Future<Output = Result<OneRequest>> + Send + 'static,
+{
+    tracing::info!("new connection!");
+    let mut buf = BytesMut::with_capacity(1024);
+    // 连续处理的流水线请求数量
+    let mut pipelined = 0;
+    // 流水线请求超过上限后不再读取新的数据, 回复 `buf` 中已经收到的请求后关闭连接
+    let mut draining = false;
+    // 已经处理的请求数量
+    let mut served = 0;
+    loop {
+        // 第一个请求之前按照请求头超时计算, 之后按照空闲超时计算
+        let idle_timeout = match served {
+            0 => config.header_timeout,
+            _ => config.keep_alive_timeout,
+        };
+        let read_task = read_stream(
+            &mut stream,
+            &mut buf,
+            idle_timeout,
+            config.header_timeout,
+            !draining,
+        );
+        let req_info = match read_task.await {
+            Ok(req_info) => req_info,
+            Err(e) => {
+                match e {
+                    GlacierError::OkErr(_kind @ Kind::EofErr) => {}
+                    GlacierError::Option => {}
+                    GlacierError::NotOkErr(_) if e.status_code() == StatusCode::BAD_REQUEST => {
+                        tracing::debug!("{:#?}", e);
+                        respond_400(&mut stream, &config).await;
                     }
-                    return Ok(());
+                    _ => tracing::debug!("{:#?}", e),
                 }
-            };
-            let mut one_req = OneRequest::new(stream, buf, req_info, addr);
+                if draining {
+                    let _ = stream.shutdown().await;
+                }
+                return Ok(());
+            }
+        };
 
-            one_req = match routes(one_req).await {
-                Ok(one_req) => one_req,
-                Err(_) => return Ok(()),
-            };
-            stream = one_req.stream;
-            buf = one_req.buf;
-            buf.clear();
+        pipelined = match req_info.pipelined {
+            true => pipelined + 1,
+            false => 0,
+        };
+        if pipelined >= config.max_pipelined && !draining {
+            tracing::debug!(pipelined, "too many pipelined requests");
+            draining = true;
+        }
+
+        let mut one_req = OneRequest::new(stream, buf, req_info, addr, config.clone());
+        one_req.init_keep_alive(config.max_requests - served);
+        served += 1;
+
+        let mut one_req = match serve_request(one_req, routes, &config).await {
+            Some(one_req) => one_req,
+            None => return Ok(()),
+        };
+        if !one_req.keep_alive() {
+            let _ = one_req.stream.shutdown().await;
+            return Ok(());
         }
+
+        // 保留已经读取到的下一个请求的数据
+        (stream, buf) = match one_req.finish().await {
+            Ok(next) => next,
+            Err(e) => {
+                tracing::debug!("{:#?}", e);
+                return Ok(());
+            }
+        };
     }
 }
 
-#[cfg(feature = "tls")]
+/// 处理一个请求, 需要关闭连接时返回 `None`
+async fn serve_request<T>(
+    mut one_req: OneRequest,
+    routes: Routes<T>,
+    config: &GlacierConfig,
+) -> Option<OneRequest>
+where
+    T: Future<Output = Result<OneRequest>> + Send + 'static,
+{
+    if let Err(e) = one_req.parse_body_kind() {
+        tracing::debug!("{:#?}", e);
+        let _ = one_req.respond_400().await;
+        return None;
+    }
+
+    if let Err(e) = one_req.normalize_path() {
+        tracing::debug!("{:#?}", e);
+        let _ = one_req.respond_400().await;
+        return None;
+    }
+
+    if let Some(location) = canonical_middle(&one_req, config) {
+        let status = match one_req.method() {
+            "GET" | "HEAD" => StatusCode::MOVED_PERMANENTLY,
+            _ => StatusCode::PERMANENT_REDIRECT,
+        };
+        return match one_req.respond(Response::redirect(&location, status)).await {
+            Ok(()) => Some(one_req),
+            Err(_) => None,
+        };
+    }
+
+    if let Some(location) = trailing_slash(&mut one_req, config.trailing_slash) {
+        return match one_req.respond_308(&location).await {
+            Ok(()) => Some(one_req),
+            Err(_) => None,
+        };
+    }
+
+    // 进入路由之前检查 `Content-Length`, 路由的限制优先于全局限制,
+    // 超过时回复413并关闭连接, 不读取请求体
+    let route_limit = unsafe { ROUTE_LIMIT };
+    if let Some(limit) = route_limit(one_req.path_for_routes()) {
+        one_req.body_limit = Some(limit);
+    }
+    match one_req.check_body_limit().await {
+        Ok(()) => {}
+        Err(GlacierError::OkErr(Kind::PayloadTooLarge)) => return Some(one_req),
+        Err(_) => return None,
+    }
+
+    routes(one_req).await.ok()
+}
+
+/// 按照 [`TrailingSlash`] 处理没有匹配到路由的路径, 需要重定向时返回新的路径
+fn trailing_slash(one_req: &mut OneRequest, policy: TrailingSlash) -> Option<String> {
+    let contain_path = unsafe { CONTAIN_PATH };
+    let path = one_req.path_for_routes();
+    if policy == TrailingSlash::Strict || path == "/" || contain_path(path) {
+        return None;
+    }
+
+    let toggled = match path.strip_suffix('/') {
+        Some(path) => String::from(path),
+        None => format!("{}/", path),
+    };
+    if !contain_path(&toggled) {
+        return None;
+    }
+
+    match policy {
+        TrailingSlash::Redirect => Some(toggled),
+        _ => {
+            one_req.route_path = Some(toggled);
+            None
+        }
+    }
+}
+
+/// 请求头不合法时回复400并关闭连接, 这时还没有 `OneRequest`
+async fn respond_400(stream: &mut GlacierStream, config: &GlacierConfig) {
+    let mut res = ResponseBuilder::new(0)
+        .status(StatusCode::BAD_REQUEST)
+        .header("Content-Length", "11")
+        .header("Connection", "close")
+        .build();
+    res.fill_date_and_server(config.server.as_deref());
+
+    let _ = stream.write_all(&res.head()).await;
+    let _ = stream.write_all(b"Bad Request").await;
+    let _ = stream.flush().await;
+    let _ = stream.shutdown().await;
+}
+
+/// 读取一个请求头
+/// # Args
+/// - `idle_timeout` - 等待请求第一个字节的时间
+/// - `header_timeout` - 收到第一个字节之后, 读取完整请求头的时间
+/// - `can_read` - 为 `false` 时只解析 `buf` 中已有的数据, 没有完整的请求头时返回 `Kind::EofErr`
 async fn read_stream(
-    stream: &mut tokio_rustls::server::TlsStream<TcpStream>,
+    stream: &mut GlacierStream,
     buf: &mut BytesMut,
+    idle_timeout: Duration,
+    header_timeout: Duration,
+    can_read: bool,
 ) -> Result<ReqInfo> {
     /* --------------------------------- // 准备工作 -------------------------------- */
-
+    // `buf` 中可能已经有上一个请求剩下的数据
     let mut pos = Vec::with_capacity(10);
     pos.push(0);
-    buf.clear();
+    let mut pipelined = !buf.is_empty();
+    let mut deadline = match buf.is_empty() {
+        true => None,
+        false => Some(Instant::now() + header_timeout),
+    };
 
     /* ------------------------ // 读取数据到buf, 然后标记buf上的位置 ------------------------ */
-    loop {
-        let read_task: _ = stream.read_buf(buf);
-        let read_task: _ = timeout(Duration::from_secs(10), read_task);
-
-        let len = match read_task.await {
-            Ok(Ok(0)) => Err(GlacierError::OkErr(Kind::EofErr))?,
-            Ok(Ok(len @ 1..)) => len,
-            Ok(Err(e)) => Err(e)?,
-            _ => Err(GlacierError::OkErr(Kind::TimeOutErr))?,
-        };
+    // 已经查找过换行符的位置
+    let mut scanned = 0;
+    let body_pos = 'read: loop {
+        while let Some(i) = buf[scanned..].iter().position(|b| *b == b'\n') {
+            let line_end = scanned + i + 1;
+            scanned = line_end;
+            if line_end < 2 || buf[line_end - 2] != b'\r' {
+                continue;
+            }
 
-        for i in (buf.len() - len..buf.len()).step_by(2) {
-            match buf[i] {
-                b'\r' => {
-                    if let Some(_x @ b'\n') = buf.get(i + 1) {
-                        pos.push(i + 2);
-                    }
+            // 空行: 请求行之前的空行直接跳过, 否则表示到了请求体
+            if line_end - pos[pos.len() - 1] == 2 {
+                match pos.len() {
+                    1 => pos[0] = line_end,
+                    _ => break 'read line_end,
                 }
-                b'\n' => {
-                    if buf[i - 1] == b'\r' {
-                        pos.push(i + 1);
-                    }
-                }
-                _ => {}
+            } else {
+                pos.push(line_end);
             }
         }
 
-        // 判断是否到了 请求体
-        let len = pos.len();
-        if len >= 2 && pos[len - 1] - pos[len - 2] == 2 {
-            pos.pop();
-            break;
+        if buf.len() > MAX_HEAD_LEN {
+            Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "request head too large",
+            ))?
         }
-    }
+
+        if !can_read {
+            Err(GlacierError::OkErr(Kind::EofErr))?
+        }
+
+        pipelined = false;
+        buf.reserve(1024);
+        let read_task = stream.read_buf(buf);
+        let read_res = match deadline {
+            Some(deadline) => timeout_at(deadline, read_task).await,
+            None => timeout(idle_timeout, read_task).await,
+        };
+        deadline.get_or_insert_with(|| Instant::now() + header_timeout);
+
+        match read_res {
+            Ok(Ok(0)) => Err(GlacierError::OkErr(Kind::EofErr))?,
+            Ok(Ok(_len @ 1..)) => {}
+            Ok(Err(e)) => Err(e)?,
+            _ => Err(GlacierError::OkErr(Kind::TimeOutErr))?,
+        };
+    };
 
     let mut lines = pos
         .as_slice()
@@ -302,15 +389,775 @@ async fn read_stream(
         tracing::debug!("lines is empty");
         GlacierError::Option
     })?;
-    let line_pos = RequestLine::parse(&buf, request_line)?;
+    let line_pos = RequestLine::parse(buf, request_line)?;
 
     // 请求头处理
     let headers_pos: Vec<[usize; 3]> = lines
-        .map(|line| RequestHeader::parse(&buf, line))
+        .map(|line| RequestHeader::parse(buf, line))
         .collect::<Result<Vec<_>>>()?;
 
     Ok(ReqInfo {
         line_pos,
         headers_pos,
+        body_pos,
+        pipelined,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncRead, AsyncWrite},
+        net::TcpStream,
+    };
+
+    trait Conn: AsyncRead + AsyncWrite + Unpin + Send {}
+    impl<S: AsyncRead + AsyncWrite + Unpin + Send> Conn for S {}
+
+    #[cfg(feature = "tls")]
+    mod tls {
+        use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName};
+        use std::sync::Arc;
+        use tokio::net::TcpStream;
+        use tokio_rustls::{client::TlsStream, TlsAcceptor, TlsConnector};
+
+        const CA: &[u8] = include_bytes!("../tests/fixtures/ca.pem");
+        const CERT: &[u8] = include_bytes!("../tests/fixtures/localhost.pem");
+        const KEY: &[u8] = include_bytes!("../tests/fixtures/localhost.key");
+
+        pub(super) fn acceptor() -> TlsAcceptor {
+            let certs = CertificateDer::pem_slice_iter(CERT)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let key = PrivateKeyDer::from_pem_slice(KEY).unwrap();
+            let config = rustls::ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .unwrap();
+            TlsAcceptor::from(Arc::new(config))
+        }
+
+        pub(super) async fn connect(stream: TcpStream) -> TlsStream<TcpStream> {
+            let mut roots = rustls::RootCertStore::empty();
+            roots
+                .add(CertificateDer::from_pem_slice(CA).unwrap())
+                .unwrap();
+            let config = rustls::ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            let name = ServerName::try_from("localhost").unwrap();
+            TlsConnector::from(Arc::new(config))
+                .connect(name, stream)
+                .await
+                .unwrap()
+        }
+    }
+
+    /// 在回环地址上启动只处理一个连接的服务器, 返回客户端的连接
+    async fn connect<T>(routes: Routes<T>, config: GlacierConfig) -> Box<dyn Conn>
+    where
+        T: Future<Output = Result<OneRequest>> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, addr) = listener.accept().await.unwrap();
+            #[cfg(feature = "tls")]
+            let stream = tls::acceptor().accept(stream).await.unwrap();
+            let _ = handle_connection(stream, routes, Arc::new(config), addr.ip()).await;
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        #[cfg(feature = "tls")]
+        let stream = tls::connect(stream).await;
+        Box::new(stream)
+    }
+
+    /// 读取到连接关闭为止, 最多等待 `wait`
+    async fn read_all(conn: &mut Box<dyn Conn>, wait: Duration) -> String {
+        let mut buf = Vec::new();
+        let _ = timeout(wait, conn.read_to_end(&mut buf)).await;
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+
+    /// 读取一个完整的响应头, 连接保持打开
+    async fn read_head(conn: &mut Box<dyn Conn>) -> String {
+        let mut buf = Vec::new();
+        while !buf.ends_with(b"\r\n\r\n") {
+            let byte = timeout(Duration::from_secs(2), conn.read_u8()).await;
+            buf.push(byte.unwrap().unwrap());
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+
+    async fn hello(mut req: OneRequest) -> Result<OneRequest> {
+        req.respond_hello().await?;
+        Ok(req)
+    }
+
+    async fn body_or_error(mut req: OneRequest) -> Result<OneRequest> {
+        match req.body().await {
+            Ok(_) => req.respond_hello().await?,
+            Err(e) => req.respond_error(&e).await?,
+        }
+        Ok(req)
+    }
+
+    async fn json_name(mut req: OneRequest) -> Result<OneRequest> {
+        match req
+            .json::<std::collections::HashMap<String, String>>()
+            .await
+        {
+            Ok(map) => {
+                let res = ResponseBuilder::new(0).body(map["name"].as_bytes()).build();
+                req.respond(res).await?
+            }
+            Err(e) => req.respond_error(&e).await?,
+        }
+        Ok(req)
+    }
+
+    async fn form_count(mut req: OneRequest) -> Result<OneRequest> {
+        match req.form::<std::collections::HashMap<String, u32>>().await {
+            Ok(map) => {
+                let count = map["count"].to_string();
+                let res = ResponseBuilder::new(0).body(count.as_bytes()).build();
+                req.respond(res).await?
+            }
+            Err(e) => req.respond_error(&e).await?,
+        }
+        Ok(req)
+    }
+
+    /// 发送一个 `POST` 请求, 返回完整的响应
+    async fn post<T>(routes: Routes<T>, content_type: Option<&str>, body: &str) -> String
+    where
+        T: Future<Output = Result<OneRequest>> + Send + 'static,
+    {
+        let mut conn = connect(routes, GlacierConfig::default()).await;
+        let content_type = content_type
+            .map(|content_type| format!("Content-Type: {}\r\n", content_type))
+            .unwrap_or_default();
+        let req = format!(
+            "POST / HTTP/1.1\r\nHost: a\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        );
+        conn.write_all(req.as_bytes()).await.unwrap();
+        read_all(&mut conn, Duration::from_secs(2)).await
+    }
+
+    async fn read_body(mut req: OneRequest) -> Result<OneRequest> {
+        let mut body = Vec::new();
+        req.body_reader().read_to_end(&mut body).await?;
+        let res = ResponseBuilder::new(0).body(&body).build();
+        req.respond(res).await?;
+        Ok(req)
+    }
+
+    async fn stream_body(mut req: OneRequest) -> Result<OneRequest> {
+        use crate::prelude::Trailers;
+        use bytes::Bytes;
+        use futures::{Stream, StreamExt};
+
+        let trailers = Trailers::new();
+        let writer = trailers.clone();
+        let mut count = 0;
+        // 只有 `Send` 的流, 例如装箱的 `dyn Stream + Send`
+        let chunks: std::pin::Pin<Box<dyn Stream<Item = Bytes> + Send>> =
+            Box::pin(futures::stream::iter(["ab", "", "cd"]).map(move |chunk| {
+                count += 1;
+                writer.insert("X-Chunks", count.to_string());
+                Bytes::from(chunk)
+            }));
+        let res = ResponseBuilder::new(0)
+            .body_stream(chunks)
+            .trailers(&["X-Chunks"], trailers)
+            .build();
+        req.respond(res).await?;
+        Ok(req)
+    }
+
+    /// `events` 推送结束后设置
+    static SSE_DONE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    async fn events(mut req: OneRequest) -> Result<OneRequest> {
+        use crate::prelude::{sse, Event};
+
+        let (tx, events) = sse::channel(4);
+        tokio::spawn(async move {
+            let _ = tx.send(Event::new().id("1").data("hello")).await;
+            // 一直持有 `tx`, 推送只会因为客户端断开而结束
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+        req.respond_sse(events.ping_interval(Duration::from_millis(100)))
+            .await?;
+        SSE_DONE.store(true, std::sync::atomic::Ordering::SeqCst);
+        Ok(req)
+    }
+
+    async fn echo(mut req: OneRequest) -> Result<OneRequest> {
+        let body = req.body().await?.to_vec();
+        let res = crate::prelude::ResponseBuilder::new(0).body(&body).build();
+        req.respond(res).await?;
+        Ok(req)
+    }
+
+    #[tokio::test]
+    async fn reject_large_content_length_before_routing() {
+        let config = GlacierConfig {
+            body_limit: Some(16),
+            ..Default::default()
+        };
+        let mut conn = connect(hello, config).await;
+        conn.write_all(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 100000\r\n\r\n")
+            .await
+            .unwrap();
+
+        // 处理函数没有读取请求体, 也不会执行
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        assert!(
+            res.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "{}",
+            res
+        );
+        assert!(res.contains("Connection: close\r\n"), "{}", res);
+        assert!(!res.contains("Hello, world!"), "{}", res);
+    }
+
+    #[tokio::test]
+    async fn canonical_redirect_keeps_origin() {
+        let config = GlacierConfig {
+            canonical_host: Some(String::from("example.com")),
+            ..Default::default()
+        };
+
+        // 请求目标不是以 `/` 开头时回复400, 不会重定向到其他域名
+        for target in ["@evil.com/x", "http://evil.com/x"] {
+            let mut conn = connect(hello, config.clone()).await;
+            let req = format!("GET {} HTTP/1.1\r\nHost: www.example.com\r\n\r\n", target);
+            conn.write_all(req.as_bytes()).await.unwrap();
+
+            let res = read_all(&mut conn, Duration::from_secs(2)).await;
+            assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", res);
+            assert!(!res.contains("evil.com"), "{}", res);
+        }
+
+        let mut conn = connect(hello, config).await;
+        conn.write_all(
+            b"GET //a/../b%20c?x=1 HTTP/1.1\r\nHost: www.example.com\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        assert!(
+            res.starts_with("HTTP/1.1 301 Moved Permanently\r\n"),
+            "{}",
+            res
+        );
+        let scheme = match cfg!(feature = "tls") {
+            true => "https",
+            false => "http",
+        };
+        let location = format!("Location: {}://example.com/b%20c?x=1\r\n", scheme);
+        assert!(res.contains(&location), "{}", res);
+
+        // `X-Forwarded-Proto` 只有开启后才使用, 并且只接受 `http` 和 `https`
+        for trust_forwarded_proto in [false, true] {
+            let config = GlacierConfig {
+                canonical_host: Some(String::from("example.com")),
+                trust_forwarded_proto,
+                ..Default::default()
+            };
+            let mut conn = connect(hello, config).await;
+            conn.write_all(
+                b"GET /x HTTP/1.1\r\nHost: www.example.com\r\n\
+                  X-Forwarded-Proto: https://evil.com/?\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+            let res = read_all(&mut conn, Duration::from_secs(2)).await;
+            let location = format!("Location: {}://example.com/x\r\n", scheme);
+            assert!(res.contains(&location), "{}", res);
+            assert!(!res.contains("evil.com"), "{}", res);
+        }
+
+        // 只设置协议时不会把不合法的 `Host` 拼进地址
+        let other_scheme = match scheme {
+            "https" => "http",
+            _ => "https",
+        };
+        let config = GlacierConfig {
+            canonical_scheme: Some(String::from(other_scheme)),
+            ..Default::default()
+        };
+        let mut conn = connect(hello, config).await;
+        conn.write_all(b"GET /x HTTP/1.1\r\nHost: good.com@evil.com\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        assert!(!res.contains("Location"), "{}", res);
+        assert!(!res.contains("evil.com"), "{}", res);
+    }
+
+    #[tokio::test]
+    async fn head_keeps_connection_in_sync() {
+        let mut conn = connect(hello, GlacierConfig::default()).await;
+        conn.write_all(b"HEAD / HTTP/1.1\r\nHost: a\r\n\r\n")
+            .await
+            .unwrap();
+        conn.write_all(b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        // `HEAD` 只有响应头, 保留 `Content-Length`, 第二个响应紧跟在后面
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        let (head, get) = res.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+        assert!(head.contains("Content-Length: 13"), "{}", res);
+        assert!(head.contains("Connection: keep-alive"), "{}", res);
+        assert!(get.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+        assert!(get.ends_with("\r\n\r\nHello, world!"), "{}", res);
+    }
+
+    #[tokio::test]
+    async fn pipelined_requests_in_one_write() {
+        let mut conn = connect(echo, GlacierConfig::default()).await;
+        conn.write_all(
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello\
+              POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nworld\r\n0\r\n\r\n\
+              GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+        // 上一个请求体之后剩下的数据是下一个请求
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        let bodies: Vec<&str> = res
+            .split("HTTP/1.1 200 OK\r\n")
+            .skip(1)
+            .map(|res| res.split_once("\r\n\r\n").unwrap().1)
+            .collect();
+        assert_eq!(bodies, ["hello", "world", ""], "{}", res);
+    }
+
+    #[tokio::test]
+    async fn unread_body_is_skipped() {
+        let mut conn = connect(hello, GlacierConfig::default()).await;
+        conn.write_all(
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 9\r\n\r\nGET /x HT\
+              GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+        // 处理函数没有读取的请求体不会被当成下一个请求
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        assert_eq!(res.matches("HTTP/1.1 200 OK\r\n").count(), 2, "{}", res);
+        assert_eq!(res.matches("Hello, world!").count(), 2, "{}", res);
+    }
+
+    #[tokio::test]
+    async fn answer_queued_requests_over_pipeline_limit() {
+        let config = GlacierConfig {
+            max_pipelined: 2,
+            ..Default::default()
+        };
+        let mut conn = connect(hello, config).await;
+        conn.write_all(&b"GET / HTTP/1.1\r\nHost: a\r\n\r\n".repeat(5))
+            .await
+            .unwrap();
+
+        // 超过上限后回复已经收到的请求, 然后关闭连接, 不等待空闲超时
+        let start = Instant::now();
+        let res = read_all(&mut conn, Duration::from_secs(3)).await;
+        assert!(start.elapsed() < Duration::from_secs(1), "{}", res);
+        assert_eq!(res.matches("Hello, world!").count(), 5, "{}", res);
+    }
+
+    #[tokio::test]
+    async fn connection_close_rules() {
+        let cases = [
+            ("HTTP/1.0", "", "close"),
+            ("HTTP/1.0", "Connection: keep-alive\r\n", "keep-alive"),
+            ("HTTP/1.1", "", "keep-alive"),
+            ("HTTP/1.1", "Connection: close\r\n", "close"),
+        ];
+        for (version, header, expected) in cases {
+            let mut conn = connect(hello, GlacierConfig::default()).await;
+            let req = format!("GET / {}\r\nHost: a\r\n{}\r\n", version, header);
+            conn.write_all(req.as_bytes()).await.unwrap();
+
+            let res = read_head(&mut conn).await;
+            let connection = format!("Connection: {}\r\n", expected);
+            assert!(
+                res.contains(&connection),
+                "{} {:?}\n{}",
+                version,
+                header,
+                res
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn idle_timeout_differs_from_header_timeout() {
+        let req = b"GET / HTTP/1.1\r\nHost: a\r\n\r\n";
+
+        // 两个请求之间按照空闲超时关闭
+        let config = GlacierConfig {
+            keep_alive_timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let mut conn = connect(hello, config).await;
+        conn.write_all(req).await.unwrap();
+        read_head(&mut conn).await;
+        let start = Instant::now();
+        let rest = read_all(&mut conn, Duration::from_secs(3)).await;
+        assert!(start.elapsed() < Duration::from_secs(1), "{}", rest);
+
+        // 收到下一个请求的第一个字节之后按照请求头超时计算
+        let config = GlacierConfig {
+            header_timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let mut conn = connect(hello, config).await;
+        conn.write_all(req).await.unwrap();
+        read_head(&mut conn).await;
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        conn.write_all(b"GET / HT").await.unwrap();
+        let start = Instant::now();
+        let rest = read_all(&mut conn, Duration::from_secs(3)).await;
+        let elapsed = start.elapsed();
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+        assert_eq!(rest, "Hello, world!");
+    }
+
+    #[tokio::test]
+    async fn body_timeout_closes_connection() {
+        let config = GlacierConfig {
+            body_timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let mut conn = connect(echo, config).await;
+        conn.write_all(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 10\r\n\r\nhel")
+            .await
+            .unwrap();
+
+        let start = Instant::now();
+        let res = read_all(&mut conn, Duration::from_secs(3)).await;
+        assert!(start.elapsed() < Duration::from_secs(1), "{}", res);
+        assert!(!res.contains("200 OK"), "{}", res);
+    }
+
+    #[tokio::test]
+    async fn expect_continue() {
+        // 读取请求体之前回复 `100 Continue`, 客户端收到后才发送请求体
+        let mut conn = connect(echo, GlacierConfig::default()).await;
+        conn.write_all(
+            b"POST / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        assert_eq!(read_head(&mut conn).await, "HTTP/1.1 100 Continue\r\n\r\n");
+        conn.write_all(b"hello").await.unwrap();
+        let res = read_head(&mut conn).await;
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+        assert!(res.contains("Connection: keep-alive\r\n"), "{}", res);
+
+        // 超过限制时直接回复413, 不发送 `100 Continue`
+        let config = GlacierConfig {
+            body_limit: Some(4),
+            ..Default::default()
+        };
+        let mut conn = connect(echo, config).await;
+        conn.write_all(
+            b"POST / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        assert!(
+            res.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "{}",
+            res
+        );
+        assert!(!res.contains("100 Continue"), "{}", res);
+        assert!(res.contains("Connection: close\r\n"), "{}", res);
+    }
+
+    #[tokio::test]
+    async fn reject_non_ascii_framing_headers() {
+        let reqs: [&[u8]; 3] = [
+            b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\xff\r\n\r\n",
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\xff\r\n\r\n",
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\nExpect: 100-continue\xff\r\n\r\n",
+        ];
+        for req in reqs {
+            let mut conn = connect(echo, GlacierConfig::default()).await;
+            conn.write_all(req).await.unwrap();
+            conn.write_all(b"5\r\nhello\r\n0\r\n\r\n").await.unwrap();
+
+            // 不能当成没有请求体, 然后把请求体当成下一个请求
+            let res = read_all(&mut conn, Duration::from_secs(2)).await;
+            assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", res);
+            assert_eq!(res.matches("HTTP/1.1").count(), 1, "{}", res);
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_bare_lf_in_header() {
+        let reqs: [&[u8]; 3] = [
+            b"GET / HTTP/1.1\r\nHost: a\nTransfer-Encoding: chunked\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: a\0b\r\n\r\n",
+            b"GET /\n HTTP/1.1\r\nHost: a\r\n\r\n",
+        ];
+        for req in reqs {
+            let mut conn = connect(hello, GlacierConfig::default()).await;
+            conn.write_all(req).await.unwrap();
+            conn.write_all(b"5\r\nhello\r\n0\r\n\r\n").await.unwrap();
+
+            let res = read_all(&mut conn, Duration::from_secs(2)).await;
+            assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", res);
+            assert!(res.contains("Connection: close\r\n"), "{}", res);
+            assert_eq!(res.matches("HTTP/1.1").count(), 1, "{}", res);
+        }
+    }
+
+    #[tokio::test]
+    async fn single_response_after_body_limit() {
+        let config = GlacierConfig {
+            body_limit: Some(8),
+            ..Default::default()
+        };
+        let mut conn = connect(body_or_error, config).await;
+        conn.write_all(
+            b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n10\r\n0123456789abcdef\r\n0\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+        // 读取时已经回复了413, `respond_error` 不会再发送一次
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        assert!(
+            res.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "{}",
+            res
+        );
+        assert_eq!(res.matches("HTTP/1.1").count(), 1, "{}", res);
+    }
+
+    #[tokio::test]
+    async fn json_request_body() {
+        let cases = [
+            (Some("application/json"), r#"{"name":"a"}"#, "200 OK", "a"),
+            (
+                Some("Application/JSON; charset=UTF-8"),
+                r#"{"name":"b"}"#,
+                "200 OK",
+                "b",
+            ),
+            (
+                Some("application/vnd.api+json"),
+                r#"{"name":"c"}"#,
+                "200 OK",
+                "c",
+            ),
+            (
+                Some("text/plain"),
+                r#"{"name":"d"}"#,
+                "415 Unsupported Media Type",
+                "",
+            ),
+            (None, r#"{"name":"e"}"#, "415 Unsupported Media Type", ""),
+            (
+                Some("application/json"),
+                r#"{"name":"#,
+                "400 Bad Request",
+                "",
+            ),
+        ];
+        for (content_type, body, status, name) in cases {
+            let res = post(json_name, content_type, body).await;
+            assert!(
+                res.starts_with(&format!("HTTP/1.1 {}\r\n", status)),
+                "{}",
+                res
+            );
+            if !name.is_empty() {
+                assert!(res.ends_with(&format!("\r\n\r\n{}", name)), "{}", res);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn form_request_body() {
+        let cases = [
+            (
+                Some("application/x-www-form-urlencoded"),
+                "count=1",
+                "200 OK",
+                "1",
+            ),
+            (
+                Some("application/x-www-form-urlencoded; charset=utf-8"),
+                "count=2",
+                "200 OK",
+                "2",
+            ),
+            (
+                Some("application/json"),
+                "count=3",
+                "415 Unsupported Media Type",
+                "",
+            ),
+            (None, "count=4", "415 Unsupported Media Type", ""),
+            (
+                Some("application/x-www-form-urlencoded"),
+                "count=x",
+                "400 Bad Request",
+                "",
+            ),
+        ];
+        for (content_type, body, status, count) in cases {
+            let res = post(form_count, content_type, body).await;
+            assert!(
+                res.starts_with(&format!("HTTP/1.1 {}\r\n", status)),
+                "{}",
+                res
+            );
+            if !count.is_empty() {
+                assert!(res.ends_with(&format!("\r\n\r\n{}", count)), "{}", res);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn body_reader_keeps_connection_reusable() {
+        let mut conn = connect(read_body, GlacierConfig::default()).await;
+
+        // 请求体分几次到达, 需要从连接中继续读取
+        conn.write_all(b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        conn.write_all(b"lo\r\n5\r\nworld\r\n0\r\n\r\nPOST / HTTP/1.1\r\nHost: a\r\nContent-Length: 8\r\n\r\nstr")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        conn.write_all(b"eamedGET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        let bodies: Vec<&str> = res
+            .split("HTTP/1.1 200 OK\r\n")
+            .skip(1)
+            .map(|res| res.split_once("\r\n\r\n").unwrap().1)
+            .collect();
+        assert_eq!(bodies, ["helloworld", "streamed", ""], "{}", res);
+    }
+
+    #[tokio::test]
+    async fn stream_response_framing() {
+        // HTTP/1.1 使用 chunked, 空的块被跳过, 最后发送尾部字段
+        let mut conn = connect(stream_body, GlacierConfig::default()).await;
+        conn.write_all(b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        let (head, body) = res.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("Transfer-Encoding: chunked\r\n"), "{}", res);
+        assert!(head.contains("Trailer: X-Chunks\r\n"), "{}", res);
+        assert!(!head.contains("Content-Length"), "{}", res);
+        assert_eq!(body, "2\r\nab\r\n2\r\ncd\r\n0\r\nX-Chunks: 3\r\n\r\n");
+
+        // HTTP/1.0 不支持 chunked, 以关闭连接表示响应体结束
+        let mut conn = connect(stream_body, GlacierConfig::default()).await;
+        conn.write_all(b"GET / HTTP/1.0\r\nHost: a\r\nConnection: keep-alive\r\n\r\n")
+            .await
+            .unwrap();
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        let (head, body) = res.split_once("\r\n\r\n").unwrap();
+        assert!(res.contains("Connection: close\r\n"), "{}", res);
+        assert!(!head.contains("Transfer-Encoding"), "{}", res);
+        assert_eq!(body, "abcd");
+
+        // `HEAD` 只发送响应头, 连接可以继续使用
+        let mut conn = connect(stream_body, GlacierConfig::default()).await;
+        conn.write_all(b"HEAD / HTTP/1.1\r\nHost: a\r\n\r\nGET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        let (head, rest) = res.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("Transfer-Encoding: chunked\r\n"), "{}", res);
+        assert!(rest.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+        assert!(
+            rest.ends_with("\r\n\r\n2\r\nab\r\n2\r\ncd\r\n0\r\nX-Chunks: 3\r\n\r\n"),
+            "{}",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn sse_stream() {
+        let mut conn = connect(events, GlacierConfig::default()).await;
+        conn.write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+            .await
+            .unwrap();
+
+        let head = read_head(&mut conn).await;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(
+            head.contains("Content-Type: text/event-stream\r\n"),
+            "{}",
+            head
+        );
+        assert!(head.contains("Connection: close\r\n"), "{}", head);
+        assert!(!head.contains("Content-Length"), "{}", head);
+
+        let mut event = [0; 19];
+        conn.read_exact(&mut event).await.unwrap();
+        assert_eq!(&event, b"id: 1\ndata: hello\n\n");
+
+        // 没有事件时按照 `ping_interval` 发送注释
+        let start = Instant::now();
+        let mut ping = [0; 8];
+        timeout(Duration::from_secs(2), conn.read_exact(&mut ping))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&ping, b": ping\n\n");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // 客户端断开后处理函数返回
+        assert!(!SSE_DONE.load(std::sync::atomic::Ordering::SeqCst));
+        conn.shutdown().await.unwrap();
+        drop(conn);
+        let done = async {
+            while !SSE_DONE.load(std::sync::atomic::Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        timeout(Duration::from_secs(2), done).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reject_non_utf8_request_target() {
+        let mut conn = connect(hello, GlacierConfig::default()).await;
+        conn.write_all(b"GET /a\xff HTTP/1.1\r\nHost: a\r\n\r\n")
+            .await
+            .unwrap();
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", res);
+
+        let mut conn = connect(hello, GlacierConfig::default()).await;
+        conn.write_all("GET /café HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n".as_bytes())
+            .await
+            .unwrap();
+        let res = read_all(&mut conn, Duration::from_secs(2)).await;
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+    }
+}
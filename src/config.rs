@@ -1,11 +1,14 @@
-use bytes::Bytes;
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
-use std::{io::Read, net::SocketAddrV4, str::FromStr};
+use std::{net::SocketAddrV4, str::FromStr, sync::Arc, time::Duration};
 use tokio::net::TcpListener;
 
 use crate::{
     error::{GlacierError, Kind},
-    prelude::{Glacier, Result, Routes, DIR_PATH, FILES_BUF},
+    prelude::{Glacier, MountOptions, Result, Routes},
+    stream::{
+        file_cache,
+        mount::{self, Mount},
+    },
 };
 //
 //
@@ -14,21 +17,85 @@ use crate::{
 //
 //
 
+/// 路径末尾 `/` 的处理方式, 只在路径没有匹配到路由时生效
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// `/hello/` 与 `/hello` 是不同的路径
+    #[default]
+    Strict,
+    /// 路径加上或去掉末尾的 `/` 能匹配到路由时, 返回308重定向
+    Redirect,
+    /// 路径加上或去掉末尾的 `/` 能匹配到路由时, 直接使用该路由
+    Ignore,
+}
+
+/// 服务器的运行配置，由 `GlacierBuilder` 设置，所有连接共享
+#[derive(Debug, Clone)]
+pub struct GlacierConfig {
+    /// 请求体的最大长度，`None` 表示不限制
+    pub(crate) body_limit: Option<u64>,
+    pub(crate) trailing_slash: TrailingSlash,
+    /// 一个连接中连续处理的流水线请求的最大数量
+    pub(crate) max_pipelined: usize,
+    /// 两个请求之间等待的最长时间
+    pub(crate) keep_alive_timeout: Duration,
+    /// 读取完整请求头的最长时间, 从收到第一个字节开始计算
+    pub(crate) header_timeout: Duration,
+    /// 读取请求体时等待下一段数据的最长时间
+    pub(crate) body_timeout: Duration,
+    /// 一个连接最多处理的请求数量
+    pub(crate) max_requests: usize,
+    /// `Server` 响应头, `None` 表示不发送
+    pub(crate) server: Option<String>,
+    /// 规范的域名和协议, 不一致的请求会被重定向
+    pub(crate) canonical_host: Option<String>,
+    pub(crate) canonical_scheme: Option<String>,
+    /// 是否使用反向代理设置的 `X-Forwarded-Proto` 作为请求的协议
+    pub(crate) trust_forwarded_proto: bool,
+}
+
+impl Default for GlacierConfig {
+    fn default() -> Self {
+        GlacierConfig {
+            body_limit: None,
+            trailing_slash: TrailingSlash::default(),
+            max_pipelined: 16,
+            keep_alive_timeout: Duration::from_secs(5),
+            header_timeout: Duration::from_secs(10),
+            body_timeout: Duration::from_secs(10),
+            max_requests: 1000,
+            server: Some(String::from("glacier")),
+            canonical_host: None,
+            canonical_scheme: None,
+            trust_forwarded_proto: false,
+        }
+    }
+}
+
 pub struct GlacierBuilder<T> {
     routes: Option<Routes<T>>,
     addr: Option<(String, u16)>,
     reuse_port: bool,
+    config: GlacierConfig,
     #[cfg(feature = "tls")]
     acceptor: Option<tokio_rustls::TlsAcceptor>,
 }
 
+impl<T> Default for GlacierBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> GlacierBuilder<T> {
     pub fn new() -> Self {
         GlacierBuilder {
             routes: None,
             addr: None,
+            #[cfg(feature = "tls")]
             acceptor: None,
             reuse_port: false,
+            config: GlacierConfig::default(),
         }
     }
 
@@ -68,38 +135,238 @@ impl<T> GlacierBuilder<T> {
         self
     }
 
-    /// 将指定目录的文件加载到缓存, 尽量不要加载大文件.
-    ///
+    /// 限制请求体的最大长度，超过则返回 `413 Payload Too Large`，
+    /// 单个路由可以通过 `#[glacier(POST, "/upload", limit = "10MB")]` 覆盖。
+    /// `Content-Length` 在读取请求体之前检查，`chunked` 请求体在读取时检查
     /// # Args
-    /// - `dir_path` - 文件夹路径
+    /// - `limit` - 最大字节数
     ///
     /// # Examples
     /// ```
     /// let glacier = GlacierBuilder::new()
     ///     .bind(3000)
-    ///     .register_dir("/public")
+    ///     .body_limit(1024 * 1024)
     ///     .serve(routes)
     ///     .build().await;
-    /// glacier.run().await.unwrap();
     /// ```
-    pub fn register_dir(self, dir_path: &'static str) -> Self {
-        tracing::info!(dir_path, "loading static resources");
+    pub fn body_limit(mut self, limit: u64) -> Self {
+        self.config.body_limit = Some(limit);
+        self
+    }
 
-        unsafe { DIR_PATH = dir_path };
+    /// 设置路径末尾 `/` 的处理方式, 默认 [`TrailingSlash::Strict`]
+    /// # Examples
+    /// ```
+    /// // 路由 "/hello" 可以通过 "/hello/" 访问
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .trailing_slash(TrailingSlash::Ignore)
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.config.trailing_slash = policy;
+        self
+    }
 
-        let entries = std::fs::read_dir(&dir_path[1..]).unwrap();
-        for entry in entries {
-            let entry = entry.unwrap();
+    /// 设置流水线 (pipelining) 中排队请求的最大数量, 默认16,
+    /// 客户端不等响应连续发送超过这个数量的请求时, 服务器不再读取新的数据,
+    /// 依次回复已经收到的请求后关闭连接, 之后的请求需要客户端重新发送
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .max_pipelined(32)
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn max_pipelined(mut self, max: usize) -> Self {
+        self.config.max_pipelined = max;
+        self
+    }
 
-            let file_path = entry.path().to_string_lossy().to_string();
-            let mut f = std::fs::File::open(entry.path()).unwrap();
+    /// 设置长连接的空闲超时时间 (默认5秒) 和一个连接最多处理的请求数量 (默认1000),
+    /// 通过 `Keep-Alive: timeout=5, max=1000` 响应头告诉客户端
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .keep_alive(Duration::from_secs(15), 100)
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn keep_alive(mut self, timeout: Duration, max_requests: usize) -> Self {
+        self.config.keep_alive_timeout = timeout;
+        self.config.max_requests = max_requests.max(1);
+        self
+    }
 
-            let mut buf = Vec::with_capacity(1024);
-            f.read_to_end(&mut buf).unwrap();
+    /// 设置读取请求头的超时时间, 默认10秒, 超时则关闭连接
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .header_timeout(Duration::from_secs(5))
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn header_timeout(mut self, timeout: Duration) -> Self {
+        self.config.header_timeout = timeout;
+        self
+    }
 
-            FILES_BUF.insert(file_path, Bytes::from(buf));
-        }
+    /// 设置读取请求体时等待下一段数据的超时时间, 默认10秒, 超时则关闭连接
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .body_timeout(Duration::from_secs(30))
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn body_timeout(mut self, timeout: Duration) -> Self {
+        self.config.body_timeout = timeout;
+        self
+    }
+
+    /// 设置 `Server` 响应头, 默认 `glacier`, 传入 `None` 则不发送
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .server_header(None)
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn server_header(mut self, value: Option<&str>) -> Self {
+        self.config.server = value.map(String::from);
+        self
+    }
+
+    /// 设置规范的域名, `Host` 不同的请求会被重定向到这个域名,
+    /// `GET`、`HEAD` 使用301, 其他方法使用308. 没有端口时只比较域名
+    /// # Examples
+    /// ```
+    /// // www.example.com -> example.com
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .canonical_host("example.com")
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn canonical_host(mut self, host: &str) -> Self {
+        self.config.canonical_host = Some(String::from(host));
+        self
+    }
+
+    /// 设置规范的协议, 例如 `https`, 协议不同的请求会被重定向.
+    /// 开启 `tls` 特性时请求的协议为 `https`, 否则为 `http`,
+    /// 在反向代理之后时见 [`GlacierBuilder::trust_forwarded_proto`]
+    /// # Examples
+    /// ```
+    /// // http://example.com -> https://example.com
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .canonical_scheme("https")
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn canonical_scheme(mut self, scheme: &str) -> Self {
+        self.config.canonical_scheme = Some(scheme.to_ascii_lowercase());
+        self
+    }
+
+    /// 使用反向代理设置的 `X-Forwarded-Proto` 作为请求的协议, 默认不使用.
+    /// 只有服务器在反向代理之后, 客户端无法直接连接时才能开启, 只接受 `http` 和 `https`
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .canonical_scheme("https")
+    ///     .trust_forwarded_proto(true)
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn trust_forwarded_proto(mut self, trust: bool) -> Self {
+        self.config.trust_forwarded_proto = trust;
+        self
+    }
 
+    /// 添加或替换扩展名对应的 MIME 类型, 用于静态文件的 `Content-Type`
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .mime_type("glb", "model/gltf-binary")
+    ///     .register_dir("/public")?
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn mime_type(self, ext: &str, mime: &str) -> Self {
+        crate::stream::mime::register(ext, mime);
+        self
+    }
+
+    /// 将文件夹挂载到请求路径前缀下, 请求时从磁盘读取文件, 常用的小文件放入缓存,
+    /// 大文件直接从磁盘流式发送. 可以挂载多个文件夹, 相同前缀会替换之前的挂载
+    ///
+    /// # Args
+    /// - `prefix` - 请求路径前缀, 必须以 `/` 开头
+    /// - `dir` - 文件夹路径
+    /// - `options` - 挂载配置
+    ///
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .mount("/static", "./public", MountOptions::new())?
+    ///     .mount("/docs", "./site", MountOptions::new().listing(true))?
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn mount(self, prefix: &str, dir: &str, options: MountOptions) -> Result<Self> {
+        let mount = Mount::new(prefix, dir, options)?;
+        #[cfg(feature = "watch")]
+        crate::stream::watch::watch(&mount)?;
+        mount::register(mount);
+        tracing::info!(prefix, dir, "mounted static resources");
+
+        Ok(self)
+    }
+
+    /// 挂载指定目录, 请求路径与目录同名, 等同于
+    /// `mount(dir_path, &dir_path[1..], MountOptions::new())`
+    ///
+    /// # Args
+    /// - `dir_path` - 文件夹路径
+    ///
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .register_dir("/public")?
+    ///     .serve(routes)
+    ///     .build().await;
+    /// glacier.run().await.unwrap();
+    /// ```
+    pub fn register_dir(self, dir_path: &str) -> Result<Self> {
+        let dir = dir_path.trim_start_matches('/');
+        self.mount(dir_path, dir, MountOptions::new())
+    }
+
+    /// 静态文件缓存的最大总字节数, 所有挂载点共享, 默认 `64MB`,
+    /// 超出时淘汰最久没有访问的文件
+    /// # Examples
+    /// ```
+    /// let glacier = GlacierBuilder::new()
+    ///     .bind(3000)
+    ///     .static_cache_size(256 * 1024 * 1024)
+    ///     .mount("/static", "./public", MountOptions::new())?
+    ///     .serve(routes)
+    ///     .build().await;
+    /// ```
+    pub fn static_cache_size(self, bytes: u64) -> Self {
+        file_cache::lock().set_capacity(bytes);
         self
     }
 
@@ -112,7 +379,7 @@ impl<T> GlacierBuilder<T> {
     ///
     /// let glacier = GlacierBuilder::new()
     ///     .bind(3000)
-    ///     .register_dir("/public")
+    ///     .register_dir("/public")?
     ///     .start_log("info", Some("my_log.log"))
     ///     .serve(routes)
     ///     .build().await;
@@ -123,10 +390,10 @@ impl<T> GlacierBuilder<T> {
         let level = tracing::Level::from_str(max_level).unwrap();
 
         let file = file_path.map(|file_path| {
-            let options: _ = std::fs::OpenOptions::new().append(true).open(&file_path);
+            let options = std::fs::OpenOptions::new().append(true).open(file_path);
             match options {
                 Ok(f) => f,
-                Err(_) => std::fs::File::create(&file_path).unwrap(),
+                Err(_) => std::fs::File::create(file_path).unwrap(),
             }
         });
 
@@ -174,6 +441,7 @@ impl<T> GlacierBuilder<T> {
 
     pub async fn build(self) -> Result<Glacier<T>> {
         let routes = self.routes.unwrap();
+        let config = Arc::new(self.config);
         let (ip, port) = self.addr.unwrap();
         let addr = SocketAddrV4::new(ip.parse().unwrap(), port);
 
@@ -194,17 +462,27 @@ impl<T> GlacierBuilder<T> {
             false => tokio::net::TcpListener::bind(addr).await?,
         };
 
-        match self.acceptor.as_ref() {
-            Some(_) => tracing::info!("start server: https://{}:{}/", ip, port),
-            None => tracing::info!("start server: http://{}:{}/", ip, port),
-        };
+        #[cfg(feature = "tls")]
+        {
+            tracing::info!("start server: https://{}:{}/", ip, port);
+            let acceptor = self.acceptor.unwrap();
+            Ok(Glacier {
+                listener,
+                routes,
+                acceptor,
+                config,
+            })
+        }
 
-        let acceptor = self.acceptor.unwrap();
-        Ok(Glacier {
-            listener,
-            routes,
-            acceptor,
-        })
+        #[cfg(not(feature = "tls"))]
+        {
+            tracing::info!("start server: http://{}:{}/", ip, port);
+            Ok(Glacier {
+                listener,
+                routes,
+                config,
+            })
+        }
     }
 }
 
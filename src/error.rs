@@ -6,6 +6,8 @@ use std::{
 
 use tokio::time::error::Elapsed;
 
+use crate::stream::status::StatusCode;
+
 //
 //
 //
@@ -65,6 +67,10 @@ pub enum Kind {
     UTF8Error,
     TimeOutErr,
     EofErr,
+    /// 请求体超过长度限制
+    PayloadTooLarge,
+    /// 请求体的 `Content-Type` 不符合要求
+    UnsupportedMediaType,
 }
 
 /* --------------------------------- // 错误工厂 -------------------------------- */
@@ -92,6 +98,35 @@ impl GlacierError {
     }
 }
 
+impl GlacierError {
+    /// 错误对应的响应代码, 用于把请求中的错误返回给客户端
+    /// # Examples
+    /// ```
+    /// let args: Args = match req.json().await {
+    ///     Ok(args) => args,
+    ///     Err(e) => {
+    ///         req.respond_error(&e).await?;
+    ///         return Ok(req);
+    ///     }
+    /// };
+    /// ```
+    pub fn status_code(&self) -> StatusCode {
+        let kind = match self {
+            GlacierError::NotOkErr(err_info) => &err_info.kind,
+            GlacierError::OkErr(kind) => kind,
+            _ => return StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        match kind {
+            Kind::InRequest | Kind::UTF8Error => StatusCode::BAD_REQUEST,
+            Kind::TimeOutErr => StatusCode::REQUEST_TIMEOUT,
+            Kind::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Kind::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 /* --------------------------------- // From -------------------------------- */
 impl From<Utf8Error> for GlacierError {
     fn from(value: Utf8Error) -> Self {
@@ -1,7 +1,11 @@
-use bytes::Bytes;
 use dashmap::DashMap;
 use prelude::OneRequest;
-use std::{net::IpAddr, sync::LazyLock, time::SystemTime};
+use std::{
+    net::IpAddr,
+    sync::{LazyLock, RwLock},
+    time::SystemTime,
+};
+use stream::mount::Mount;
 
 pub mod client;
 pub mod config;
@@ -19,18 +23,15 @@ pub mod stream;
 pub type Result<T> = core::result::Result<T, error::GlacierError>;
 pub type Routes<T> = fn(OneRequest) -> T;
 
-/// 静态资源路径
-pub static mut DIR_PATH: &'static str = "";
-
-/// 静态资源缓存
-pub static FILES_BUF: LazyLock<DashMap<String, Bytes>> = LazyLock::new(|| DashMap::new());
+/// 静态资源的挂载点
+pub static MOUNTS: LazyLock<RwLock<Vec<Mount>>> = LazyLock::new(|| RwLock::new(Vec::new()));
 
 /// 访问者ip，用来记录上一次的访问时间戳
 /// # Args
 /// - `ip` - 访问者ip
 /// - `systemtime` - 上一次访问时间戳
 /// - `count` - 异常（访问间隔过短）次数
-pub static IP: LazyLock<DashMap<IpAddr, (SystemTime, usize)>> = LazyLock::new(|| DashMap::new());
+pub static IP: LazyLock<DashMap<IpAddr, (SystemTime, usize)>> = LazyLock::new(DashMap::new);
 
 /// 路由（待定）
 pub static mut CONTAIN_PATH: fn(&str) -> bool = {
@@ -39,6 +40,15 @@ pub static mut CONTAIN_PATH: fn(&str) -> bool = {
     }
     temp
 };
+
+/// 路由的请求体长度限制, 由 `#[glacier(POST, "/upload", limit = "10MB")]` 生成,
+/// 在进入路由之前检查 `Content-Length`
+pub static mut ROUTE_LIMIT: fn(&str) -> Option<u64> = {
+    fn temp(_x: &str) -> Option<u64> {
+        None
+    }
+    temp
+};
 //
 //
 //
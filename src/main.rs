@@ -27,7 +27,7 @@ fn main() -> Result<()> {
     //         .unwrap()
     //         .bind(443)
     //         // .start_log("debug", None)
-    //         // .register_dir("/public")
+    //         // .register_dir("/public")?
     //         .server(routes)
     //         .build()
     //         .await;
@@ -44,7 +44,7 @@ fn main() -> Result<()> {
             rt.block_on(async {
                 let glacier = GlacierBuilder::new()
                     .start_log("debug", None)
-                    // .register_dir("/public")
+                    // .register_dir("/public")?
                     .open_tls()
                     .unwrap()
                     .server(routes)
@@ -0,0 +1,75 @@
+use crate::{
+    config::GlacierConfig,
+    prelude::OneRequest,
+    stream::{header::Host, path},
+};
+//
+//
+//
+//
+//
+
+/// 没有 `X-Forwarded-Proto` 时请求的协议
+const DEFAULT_SCHEME: &str = match cfg!(feature = "tls") {
+    true => "https",
+    false => "http",
+};
+
+/// 规范域名、协议中间件，请求的域名或协议与 `GlacierBuilder` 上配置的不同时，返回重定向的地址
+/// # Args
+/// - `req` - 请求
+/// - `config` - 由 `canonical_host`、`canonical_scheme`、`trust_forwarded_proto` 设置
+///
+/// 需要在 `normalize_path` 之后调用, 重定向的地址由规范化后的路径和查询参数组成,
+/// 不会把 `@evil.com/x` 这样的请求目标拼进地址
+pub(crate) fn canonical_middle(req: &OneRequest, config: &GlacierConfig) -> Option<String> {
+    if config.canonical_host.is_none() && config.canonical_scheme.is_none() {
+        return None;
+    }
+
+    let headers = req.headers();
+    let host: Option<Host> = headers.typed();
+    // 只接受 `http` 和 `https`, 其他值会被拼进 `Location`
+    let scheme = headers
+        .get("X-Forwarded-Proto")
+        .filter(|_| config.trust_forwarded_proto)
+        .and_then(|proto| proto.split(',').next())
+        .map(str::trim)
+        .and_then(|proto| {
+            ["http", "https"]
+                .into_iter()
+                .find(|scheme| proto.eq_ignore_ascii_case(scheme))
+        })
+        .unwrap_or(DEFAULT_SCHEME);
+
+    let host_matches = match (&config.canonical_host, &host) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(canonical), Some(host)) => match canonical.contains(':') {
+            true => headers
+                .get("Host")
+                .is_some_and(|value| value.eq_ignore_ascii_case(canonical)),
+            false => host.host.eq_ignore_ascii_case(canonical),
+        },
+    };
+    let scheme_matches = config
+        .canonical_scheme
+        .as_ref()
+        .is_none_or(|canonical| scheme.eq_ignore_ascii_case(canonical));
+    if host_matches && scheme_matches {
+        return None;
+    }
+
+    // 只改协议时去掉端口, 原来的端口对新的协议没有意义
+    let target_host = match &config.canonical_host {
+        Some(canonical) => canonical.as_str(),
+        None => host.as_ref().map(|host| host.host.as_str())?,
+    };
+    let target_scheme = config.canonical_scheme.as_deref().unwrap_or(scheme);
+
+    let path = path::encode(req.path_for_routes());
+    Some(match req.query() {
+        Some(query) => format!("{}://{}{}?{}", target_scheme, target_host, path, query),
+        None => format!("{}://{}{}", target_scheme, target_host, path),
+    })
+}
@@ -3,11 +3,11 @@ use crate::{
     prelude::{GlacierError, OneRequest, Result, IP},
 };
 use std::time::SystemTime;
-///
-///
-///
-///
-///
+//
+//
+//
+//
+//
 
 /// ip限制中间件，限制某ip连续两次访问的最小间隔，超过最小间隔次数过多则抛出错误
 /// # Args
@@ -1 +1,2 @@
+pub mod canonical_middle;
 pub mod ip_middle;
\ No newline at end of file
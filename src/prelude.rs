@@ -1,12 +1,20 @@
 pub use crate::client::Glacier;
-pub use crate::config::GlacierBuilder;
+pub use crate::config::{GlacierBuilder, TrailingSlash};
 pub use crate::error::{GlacierError, Kind};
 pub use crate::middles::ip_middle::ip_middle;
+pub use crate::stream::broadcast::{Broadcast, SlowConsumer};
+pub use crate::stream::extensions::Extensions;
+pub use crate::stream::header::{Header, HeaderMap};
+pub use crate::stream::mount::MountOptions;
+pub use crate::stream::multipart::{Multipart, SpoolOptions};
 pub use crate::stream::request::OneRequest;
 pub use crate::stream::response::ContentType;
 pub use crate::stream::response::Response;
 pub use crate::stream::response::ResponseBuilder;
+pub use crate::stream::response::{Body, ResponseHeaders, Trailers};
+pub use crate::stream::sse::{self, Event, SseSender};
+pub use crate::stream::status::StatusCode;
 pub use crate::Result;
 pub use crate::Routes;
-pub use crate::{CONTAIN_PATH, DIR_PATH, FILES_BUF, IP};
+pub use crate::{CONTAIN_PATH, IP, MOUNTS, ROUTE_LIMIT};
 pub use glacier_macro::{glacier, main};
@@ -0,0 +1,384 @@
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::prelude::{GlacierError, Kind, Result};
+
+/// 分块长度行、trailer 行的最大长度
+const MAX_LINE_LEN: usize = 4096;
+/// trailer 的最大数量
+const MAX_TRAILERS: usize = 64;
+
+/* ----------------------------------- BodyKind ----------------------------------- */
+/// 请求体的分帧方式
+#[derive(Debug)]
+pub(crate) enum BodyKind {
+    /// 没有请求体，或者请求体已经读完
+    Done,
+    /// `Content-Length`，记录剩余的长度
+    Length(u64),
+    /// `Transfer-Encoding: chunked`
+    Chunked(ChunkedDecoder),
+}
+
+impl BodyKind {
+    /// 根据 `Content-Length` 和 `Transfer-Encoding` 判断请求体的分帧方式
+    /// # Args
+    /// - `content_length` - 所有 `Content-Length` 的值
+    /// - `transfer_encoding` - 所有 `Transfer-Encoding` 的值
+    pub(crate) fn parse<'a>(
+        content_length: impl Iterator<Item = &'a str>,
+        transfer_encoding: impl Iterator<Item = &'a str>,
+    ) -> Result<BodyKind> {
+        let mut length = None;
+        for value in content_length {
+            // `Content-Length: 10, 10` 或者多个相同的 `Content-Length` 是允许的
+            for value in value.split(',') {
+                let value = value.trim();
+                let len = match value.bytes().all(|b| b.is_ascii_digit()) {
+                    true => value.parse::<u64>().ok(),
+                    false => None,
+                };
+                match (len, length) {
+                    (None, _) => Err(GlacierError::not_ok_err(
+                        Kind::InRequest,
+                        format!("invalid Content-Length: {}", value),
+                    ))?,
+                    (Some(len), Some(prev)) if len != prev => Err(GlacierError::not_ok_err(
+                        Kind::InRequest,
+                        "conflicting Content-Length",
+                    ))?,
+                    (Some(len), _) => length = Some(len),
+                }
+            }
+        }
+
+        let mut codings = transfer_encoding
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty())
+            .peekable();
+
+        if codings.peek().is_none() {
+            return match length {
+                Some(0) | None => Ok(BodyKind::Done),
+                Some(len) => Ok(BodyKind::Length(len)),
+            };
+        }
+
+        // 同时出现 `Content-Length` 和 `Transfer-Encoding` 可能是请求走私
+        if length.is_some() {
+            Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "both Content-Length and Transfer-Encoding",
+            ))?
+        }
+
+        // 只支持单独的 `chunked`
+        match (codings.next(), codings.next()) {
+            (Some(coding), None) if coding.eq_ignore_ascii_case("chunked") => {
+                Ok(BodyKind::Chunked(ChunkedDecoder::new()))
+            }
+            _ => Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "unsupported Transfer-Encoding",
+            )),
+        }
+    }
+
+    /// 请求体是否已经读完
+    pub(crate) fn is_done(&self) -> bool {
+        match self {
+            BodyKind::Done => true,
+            BodyKind::Length(_) => false,
+            BodyKind::Chunked(decoder) => decoder.is_done(),
+        }
+    }
+
+    /// 从 `src` 中取出一段解码后的请求体
+    ///
+    /// 返回 `Ok(None)` 时，若 [`BodyKind::is_done`] 为 `false` 则需要继续读取数据
+    pub(crate) fn decode(
+        &mut self,
+        src: &mut BytesMut,
+        trailers: &mut Vec<(String, String)>,
+    ) -> Result<Option<Bytes>> {
+        match self {
+            BodyKind::Done => Ok(None),
+            BodyKind::Length(remaining) => {
+                if src.is_empty() {
+                    return Ok(None);
+                }
+                let len = (*remaining).min(src.len() as u64);
+                *remaining -= len;
+                let data = src.split_to(len as usize).freeze();
+                if *remaining == 0 {
+                    *self = BodyKind::Done;
+                }
+                Ok(Some(data))
+            }
+            BodyKind::Chunked(decoder) => decoder.decode(src, trailers),
+        }
+    }
+}
+
+/* ----------------------------------- ChunkedDecoder ----------------------------------- */
+#[derive(Debug)]
+enum ChunkedState {
+    /// 分块长度行，`1a;name=value\r\n`
+    Size,
+    /// 分块数据，记录剩余的长度
+    Data(u64),
+    /// 分块数据后面的 `\r\n`
+    DataEnd,
+    /// 最后一个分块后面的 trailer
+    Trailer,
+    Done,
+}
+
+/// `Transfer-Encoding: chunked` 解码器
+#[derive(Debug)]
+pub(crate) struct ChunkedDecoder {
+    state: ChunkedState,
+}
+
+impl ChunkedDecoder {
+    pub(crate) fn new() -> Self {
+        ChunkedDecoder {
+            state: ChunkedState::Size,
+        }
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        matches!(self.state, ChunkedState::Done)
+    }
+
+    pub(crate) fn decode(
+        &mut self,
+        src: &mut BytesMut,
+        trailers: &mut Vec<(String, String)>,
+    ) -> Result<Option<Bytes>> {
+        loop {
+            match self.state {
+                ChunkedState::Size => {
+                    let line = match take_line(src)? {
+                        Some(line) => line,
+                        None => return Ok(None),
+                    };
+                    let size = parse_chunk_size(&line)?;
+                    self.state = match size {
+                        0 => ChunkedState::Trailer,
+                        size => ChunkedState::Data(size),
+                    };
+                }
+                ChunkedState::Data(remaining) => {
+                    if src.is_empty() {
+                        return Ok(None);
+                    }
+                    let len = remaining.min(src.len() as u64);
+                    let data = src.split_to(len as usize).freeze();
+                    self.state = match remaining - len {
+                        0 => ChunkedState::DataEnd,
+                        remaining => ChunkedState::Data(remaining),
+                    };
+                    return Ok(Some(data));
+                }
+                ChunkedState::DataEnd => {
+                    if src.len() < 2 {
+                        return Ok(None);
+                    }
+                    if &src[..2] != b"\r\n" {
+                        Err(GlacierError::not_ok_err(
+                            Kind::InRequest,
+                            "missing CRLF after chunk data",
+                        ))?
+                    }
+                    src.advance(2);
+                    self.state = ChunkedState::Size;
+                }
+                ChunkedState::Trailer => {
+                    let line = match take_line(src)? {
+                        Some(line) => line,
+                        None => return Ok(None),
+                    };
+                    if line.is_empty() {
+                        self.state = ChunkedState::Done;
+                        return Ok(None);
+                    }
+                    if trailers.len() >= MAX_TRAILERS {
+                        Err(GlacierError::not_ok_err(
+                            Kind::InRequest,
+                            "too many trailers",
+                        ))?
+                    }
+                    trailers.push(parse_trailer(&line)?);
+                }
+                ChunkedState::Done => return Ok(None),
+            }
+        }
+    }
+}
+
+/// 从 `src` 中取出一行，不包含 `\r\n`
+fn take_line(src: &mut BytesMut) -> Result<Option<BytesMut>> {
+    match src.windows(2).position(|w| w == b"\r\n") {
+        Some(pos) => {
+            let line = src.split_to(pos);
+            src.advance(2);
+            Ok(Some(line))
+        }
+        None if src.len() > MAX_LINE_LEN => Err(GlacierError::not_ok_err(
+            Kind::InRequest,
+            "chunk line too long",
+        )),
+        None => Ok(None),
+    }
+}
+
+/// 解析分块长度，忽略分块扩展 `;name=value`.
+/// 长度前后不允许有空白, 只有分块扩展的 `;` 之前可以有空白 (RFC 9112 7.1.1),
+/// 宽松的解析可能被用来走私请求
+fn parse_chunk_size(line: &[u8]) -> Result<u64> {
+    let size = match line.iter().position(|b| *b == b';') {
+        Some(pos) => {
+            let bws = line[..pos]
+                .iter()
+                .rev()
+                .take_while(|b| matches!(b, b' ' | b'\t'));
+            &line[..pos - bws.count()]
+        }
+        None => line,
+    };
+
+    let invalid = || GlacierError::not_ok_err(Kind::InRequest, "invalid chunk size");
+    if size.is_empty() || size.len() > 16 {
+        Err(invalid())?
+    }
+    size.iter().try_fold(0u64, |acc, b| {
+        let digit = (*b as char).to_digit(16).ok_or_else(invalid)?;
+        Ok(acc << 4 | digit as u64)
+    })
+}
+
+fn parse_trailer(line: &[u8]) -> Result<(String, String)> {
+    let line = std::str::from_utf8(line)?;
+    match line.split_once(':') {
+        Some((key, value)) if !key.is_empty() && !key.contains([' ', '\t']) => {
+            Ok((String::from(key), String::from(value.trim())))
+        }
+        _ => Err(GlacierError::not_ok_err(
+            Kind::InRequest,
+            format!("invalid trailer: {}", line),
+        )),
+    }
+}
+
+/* ----------------------------------- BodyReader ----------------------------------- */
+/// 请求体的流，由 `OneRequest::body_stream` 创建
+pub type BodyStream<'a> = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send + 'a>>;
+
+/// 以 `AsyncRead` 的方式读取请求体，由 `OneRequest::body_reader` 创建
+pub struct BodyReader<'a> {
+    stream: BodyStream<'a>,
+    chunk: Bytes,
+}
+
+impl<'a> BodyReader<'a> {
+    pub(crate) fn new(stream: BodyStream<'a>) -> Self {
+        BodyReader {
+            stream,
+            chunk: Bytes::new(),
+        }
+    }
+}
+
+impl AsyncRead for BodyReader<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.chunk.is_empty() {
+            match ready!(self.stream.poll_next_unpin(cx)) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(e)) => return Poll::Ready(Err(io::Error::other(format!("{:?}", e)))),
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let len = buf.remaining().min(self.chunk.len());
+        buf.put_slice(&self.chunk.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Decoded = (Vec<u8>, Vec<(String, String)>);
+
+    fn decode_all(kind: &mut BodyKind, input: &[&[u8]]) -> Result<Decoded> {
+        let mut src = BytesMut::new();
+        let mut body = Vec::new();
+        let mut trailers = Vec::new();
+        for part in input {
+            src.extend_from_slice(part);
+            while let Some(data) = kind.decode(&mut src, &mut trailers)? {
+                body.extend_from_slice(&data);
+            }
+        }
+        assert!(kind.is_done());
+        Ok((body, trailers))
+    }
+
+    #[test]
+    fn chunked_with_extensions_and_trailers() {
+        let mut kind = BodyKind::parse(None.into_iter(), Some("chunked").into_iter()).unwrap();
+        let (body, trailers) = decode_all(
+            &mut kind,
+            &[
+                b"5;name=val\r\nhel",
+                b"lo\r\n6\r\n world\r",
+                b"\n0\r\nExpires: never\r\n",
+                b"\r\n",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(body, b"hello world");
+        assert_eq!(trailers, [(String::from("Expires"), String::from("never"))]);
+    }
+
+    #[test]
+    fn reject_length_with_transfer_encoding() {
+        let kind = BodyKind::parse(Some("5").into_iter(), Some("chunked").into_iter());
+        assert!(kind.is_err());
+        let kind = BodyKind::parse(Some("5, 6").into_iter(), None.into_iter());
+        assert!(kind.is_err());
+        let kind = BodyKind::parse(None.into_iter(), Some("gzip, chunked").into_iter());
+        assert!(kind.is_err());
+    }
+
+    #[test]
+    fn reject_invalid_chunk() {
+        let mut kind = BodyKind::parse(None.into_iter(), Some("chunked").into_iter()).unwrap();
+        assert!(decode_all(&mut kind, &[b"zz\r\n"]).is_err());
+        let mut kind = BodyKind::parse(None.into_iter(), Some("chunked").into_iter()).unwrap();
+        assert!(decode_all(&mut kind, &[b"2\r\nabc\r\n"]).is_err());
+    }
+
+    #[test]
+    fn reject_whitespace_around_chunk_size() {
+        for line in [&b" 5"[..], b"5 ", b"5\t", b"\t5;a=b"] {
+            assert!(parse_chunk_size(line).is_err());
+        }
+        assert_eq!(parse_chunk_size(b"5").unwrap(), 5);
+        assert_eq!(parse_chunk_size(b"a \t;name=val").unwrap(), 10);
+    }
+}
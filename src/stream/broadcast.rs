@@ -0,0 +1,205 @@
+use dashmap::DashMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::stream::sse::{Event, SseReceiver};
+
+/// 订阅者的队列满了时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SlowConsumer {
+    /// 丢弃这条消息, 订阅者之后还能收到新的消息
+    #[default]
+    Drop,
+    /// 取消订阅, 订阅者收完队列中的消息后结束, SSE 连接随之关闭
+    Disconnect,
+}
+
+struct Subscriber<T> {
+    tx: mpsc::Sender<T>,
+}
+
+struct Inner<T> {
+    topics: DashMap<String, Vec<Subscriber<T>>>,
+    capacity: usize,
+    policy: SlowConsumer,
+    /// 因为队列满了而丢弃的消息数量
+    dropped: AtomicU64,
+    /// 因为队列满了而取消订阅的数量
+    disconnected: AtomicU64,
+}
+
+/// 按主题广播消息, 每个订阅者有自己的有界队列, 慢的订阅者不会阻塞其他订阅者.
+/// 克隆后共享同一组主题
+/// # Examples
+/// ```
+/// static HUB: LazyLock<Broadcast> = LazyLock::new(|| Broadcast::new(64, SlowConsumer::Drop));
+///
+/// #[glacier(GET, "/rooms/lobby")]
+/// async fn lobby(mut req: OneRequest) {
+///     let events = HUB.subscribe_sse("lobby");
+///     req.respond_sse(events).await?;
+/// }
+///
+/// // 其他地方
+/// HUB.publish("lobby", Event::new().data("hello"));
+/// ```
+pub struct Broadcast<T = Event> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Clone for Broadcast<T> {
+    fn clone(&self) -> Self {
+        Broadcast {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static> Broadcast<T> {
+    /// # Args
+    /// - `capacity` - 每个订阅者队列的最大长度
+    /// - `policy` - 队列满了时的处理方式
+    pub fn new(capacity: usize, policy: SlowConsumer) -> Self {
+        Broadcast {
+            inner: Arc::new(Inner {
+                topics: DashMap::new(),
+                capacity: capacity.max(1),
+                policy,
+                dropped: AtomicU64::new(0),
+                disconnected: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// 订阅主题, 接收端被丢弃后自动取消订阅.
+    /// 同时清理已经关闭的订阅者, 很少发布消息的主题也不会一直堆积
+    pub fn subscribe(&self, topic: &str) -> mpsc::Receiver<T> {
+        let (tx, rx) = mpsc::channel(self.inner.capacity);
+        let mut subscribers = self.inner.topics.entry(String::from(topic)).or_default();
+        subscribers.retain(|subscriber| !subscriber.tx.is_closed());
+        subscribers.push(Subscriber { tx });
+
+        rx
+    }
+
+    /// 向主题的所有订阅者发送消息, 不会等待, 返回成功放入队列的数量
+    pub fn publish(&self, topic: &str, msg: T) -> usize {
+        let mut delivered = 0;
+        let Some(mut subscribers) = self.inner.topics.get_mut(topic) else {
+            return 0;
+        };
+
+        subscribers.retain(|subscriber| match subscriber.tx.try_send(msg.clone()) {
+            Ok(()) => {
+                delivered += 1;
+                true
+            }
+            Err(TrySendError::Full(_)) => match self.inner.policy {
+                SlowConsumer::Drop => {
+                    self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                SlowConsumer::Disconnect => {
+                    self.inner.disconnected.fetch_add(1, Ordering::Relaxed);
+                    false
+                }
+            },
+            Err(TrySendError::Closed(_)) => false,
+        });
+
+        let empty = subscribers.is_empty();
+        drop(subscribers);
+        if empty {
+            self.inner
+                .topics
+                .remove_if(topic, |_, subscribers| subscribers.is_empty());
+        }
+
+        delivered
+    }
+
+    /// 主题当前的订阅者数量
+    pub fn subscriber_count(&self, topic: &str) -> usize {
+        self.inner.topics.get(topic).map_or(0, |subscribers| {
+            subscribers.iter().filter(|s| !s.tx.is_closed()).count()
+        })
+    }
+
+    /// 所有主题的订阅者数量
+    pub fn total_subscribers(&self) -> usize {
+        self.inner
+            .topics
+            .iter()
+            .map(|subscribers| subscribers.iter().filter(|s| !s.tx.is_closed()).count())
+            .sum()
+    }
+
+    /// 有订阅者的主题数量
+    pub fn topic_count(&self) -> usize {
+        self.inner.topics.len()
+    }
+
+    /// 因为队列满了而丢弃的消息数量
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 因为队列满了而取消订阅的数量
+    pub fn disconnected(&self) -> u64 {
+        self.inner.disconnected.load(Ordering::Relaxed)
+    }
+}
+
+impl Broadcast<Event> {
+    /// 订阅主题, 返回值交给 [`OneRequest::respond_sse`](crate::prelude::OneRequest::respond_sse)
+    pub fn subscribe_sse(&self, topic: &str) -> SseReceiver {
+        SseReceiver::new(self.subscribe(topic))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fan_out_and_slow_consumers() {
+        let hub: Broadcast<u32> = Broadcast::new(1, SlowConsumer::Drop);
+        let mut a = hub.subscribe("t");
+        let mut b = hub.subscribe("t");
+        assert_eq!(hub.subscriber_count("t"), 2);
+
+        assert_eq!(hub.publish("t", 1), 2);
+        assert_eq!(a.recv().await, Some(1));
+        // b 的队列满了, 消息被丢弃
+        assert_eq!(hub.publish("t", 2), 1);
+        assert_eq!(hub.dropped(), 1);
+        assert_eq!(b.recv().await, Some(1));
+        assert_eq!(a.recv().await, Some(2));
+
+        drop(a);
+        assert_eq!(hub.subscriber_count("t"), 1);
+        assert_eq!(hub.publish("nobody", 3), 0);
+
+        let hub: Broadcast<u32> = Broadcast::new(1, SlowConsumer::Disconnect);
+        let mut slow = hub.subscribe("t");
+        hub.publish("t", 1);
+        hub.publish("t", 2);
+        assert_eq!(hub.disconnected(), 1);
+        assert_eq!(hub.topic_count(), 0);
+        assert_eq!(slow.recv().await, Some(1));
+        assert_eq!(slow.recv().await, None);
+    }
+
+    #[test]
+    fn subscribe_prunes_closed_subscribers() {
+        let hub: Broadcast<u32> = Broadcast::new(1, SlowConsumer::Drop);
+        for _ in 0..100 {
+            drop(hub.subscribe("t"));
+        }
+        let _rx = hub.subscribe("t");
+        assert_eq!(hub.inner.topics.get("t").unwrap().len(), 1);
+    }
+}
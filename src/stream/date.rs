@@ -0,0 +1,82 @@
+use std::{
+    sync::RwLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// 缓存的 `Date` 响应头, 同一秒内的响应共用, 每秒最多格式化一次
+static CACHED: RwLock<(u64, String)> = RwLock::new((0, String::new()));
+
+/// 当前时间, 用于 `Date` 响应头
+pub(crate) fn now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    if let Ok(cached) = CACHED.read() {
+        if cached.0 == secs {
+            return cached.1.clone();
+        }
+    }
+
+    let date = fmt_secs(secs);
+    if let Ok(mut cached) = CACHED.write() {
+        *cached = (secs, date.clone());
+    }
+    date
+}
+
+/// 格式化为 HTTP 日期 (RFC 9110 IMF-fixdate)
+/// # Examples
+/// ```
+/// fmt_http_date(UNIX_EPOCH + Duration::from_secs(784111777)) -> "Sun, 06 Nov 1994 08:49:37 GMT"
+/// ```
+pub fn fmt_http_date(time: SystemTime) -> String {
+    fmt_secs(time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()))
+}
+
+fn fmt_secs(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let secs_of_day = secs % 86400;
+    // 1970-01-01 是星期四
+    let weekday = (days + 4) % 7;
+
+    // 由天数计算公历日期, 见 http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[weekday as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imf_fixdate() {
+        assert_eq!(fmt_secs(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(fmt_secs(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(fmt_secs(951782400), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(now().len(), 29);
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+/// 请求的扩展数据, 以类型为键, 每种类型最多保存一个值,
+/// 用于中间件向处理函数传递数据
+/// # Examples
+/// ```
+/// struct User {
+///     name: String,
+/// }
+///
+/// // 中间件
+/// req.extensions_mut().insert(User { name });
+///
+/// // 处理函数
+/// let user = req.extensions().get::<User>().unwrap();
+/// ```
+#[derive(Default)]
+pub struct Extensions {
+    // 大部分请求没有扩展数据, 用到时才分配
+    map: Option<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Extensions { map: None }
+    }
+
+    /// 插入一个值, 返回之前同类型的值
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .get_or_insert_with(HashMap::new)
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|prev| prev.downcast().ok())
+            .map(|prev| *prev)
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .as_ref()?
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .as_mut()?
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// 取出一个值
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .as_mut()?
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.as_ref().map_or(0, HashMap::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(map) = &mut self.map {
+            map.clear();
+        }
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_get_remove() {
+        #[derive(Debug, PartialEq)]
+        struct RequestId(u64);
+
+        let mut extensions = Extensions::new();
+        assert!(extensions.get::<RequestId>().is_none());
+
+        assert_eq!(extensions.insert(RequestId(1)), None);
+        assert_eq!(extensions.insert(RequestId(2)), Some(RequestId(1)));
+        extensions.insert(String::from("user"));
+        assert_eq!(extensions.len(), 2);
+
+        extensions.get_mut::<RequestId>().unwrap().0 += 1;
+        assert_eq!(extensions.get::<RequestId>(), Some(&RequestId(3)));
+        assert_eq!(extensions.remove::<String>().as_deref(), Some("user"));
+        assert!(!extensions.contains::<String>());
+    }
+}
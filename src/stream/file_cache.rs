@@ -0,0 +1,160 @@
+use bytes::Bytes;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::Metadata,
+    sync::{LazyLock, Mutex, MutexGuard},
+    time::UNIX_EPOCH,
+};
+
+/// 静态文件缓存, 所有挂载点共享
+static FILE_CACHE: LazyLock<Mutex<FileCache>> =
+    LazyLock::new(|| Mutex::new(FileCache::new(64 * 1024 * 1024)));
+
+pub(crate) fn lock() -> MutexGuard<'static, FileCache> {
+    FILE_CACHE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+struct Cached {
+    bytes: Bytes,
+    etag: String,
+    /// 最后一次访问的序号
+    tick: u64,
+}
+
+/// 按总字节数限制大小的 LRU 缓存, 超出容量时淘汰最久没有访问的文件
+pub(crate) struct FileCache {
+    files: HashMap<String, Cached>,
+    /// 访问序号到文件路径, 第一个是最久没有访问的
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    size: u64,
+    capacity: u64,
+}
+
+impl FileCache {
+    fn new(capacity: u64) -> Self {
+        FileCache {
+            files: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            size: 0,
+            capacity,
+        }
+    }
+
+    /// 设置容量, 超出的部分立即淘汰
+    pub(crate) fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// 查找文件, 返回内容和 ETag
+    pub(crate) fn get(&mut self, path: &str) -> Option<(Bytes, String)> {
+        self.tick += 1;
+        let cached = self.files.get_mut(path)?;
+        self.order.remove(&cached.tick);
+        self.order.insert(self.tick, String::from(path));
+        cached.tick = self.tick;
+
+        Some((cached.bytes.clone(), cached.etag.clone()))
+    }
+
+    /// 放入文件, 比容量大的文件不会被缓存
+    pub(crate) fn insert(&mut self, path: &str, bytes: Bytes, etag: String) {
+        self.remove(path);
+        if bytes.len() as u64 > self.capacity {
+            return;
+        }
+
+        self.tick += 1;
+        self.size += bytes.len() as u64;
+        self.order.insert(self.tick, String::from(path));
+        let tick = self.tick;
+        self.files
+            .insert(String::from(path), Cached { bytes, etag, tick });
+        self.evict();
+    }
+
+    #[cfg(feature = "watch")]
+    pub(crate) fn contains(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    /// 删除目录中的所有文件, `dir` 以 `/` 结尾
+    #[cfg(feature = "watch")]
+    pub(crate) fn remove_dir(&mut self, dir: &str) {
+        let paths: Vec<String> = self
+            .files
+            .keys()
+            .filter(|path| path.starts_with(dir))
+            .cloned()
+            .collect();
+        for path in paths {
+            self.remove(&path);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, path: &str) {
+        if let Some(cached) = self.files.remove(path) {
+            self.order.remove(&cached.tick);
+            self.size -= cached.bytes.len() as u64;
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            let Some((_, path)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(cached) = self.files.remove(&path) {
+                self.size -= cached.bytes.len() as u64;
+            }
+        }
+    }
+}
+
+/// 根据文件长度和修改时间生成弱 ETag, 文件改变后 ETag 随之改变
+pub(crate) fn etag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_nanos());
+
+    format!("W/\"{:x}-{:x}\"", metadata.len(), modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru_bounded_by_bytes() {
+        let mut cache = FileCache::new(10);
+        let etag = || String::from("W/\"1\"");
+        cache.insert("a", Bytes::from_static(b"aaaa"), etag());
+        cache.insert("b", Bytes::from_static(b"bbbb"), etag());
+        assert!(cache.get("a").is_some());
+
+        // b 最久没有访问, 被淘汰
+        cache.insert("c", Bytes::from_static(b"cccc"), etag());
+        assert!(cache.get("b").is_none());
+        assert_eq!(&cache.get("a").unwrap().0[..], b"aaaa");
+        assert_eq!(cache.size, 8);
+
+        // 比容量大的文件不缓存
+        cache.insert("big", Bytes::from_static(b"0123456789ab"), etag());
+        assert!(cache.get("big").is_none());
+
+        #[cfg(feature = "watch")]
+        {
+            cache.insert("d/e", Bytes::from_static(b"e"), etag());
+            cache.remove_dir("d/");
+            assert!(!cache.contains("d/e"));
+        }
+
+        cache.set_capacity(4);
+        assert_eq!(cache.files.len(), 1);
+        assert!(cache.get("a").is_some());
+    }
+}
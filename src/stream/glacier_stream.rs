@@ -1 +1,9 @@
+use tokio::net::TcpStream;
 
+/// 服务器与客户端之间的连接，开启 `tls` 时为 `TlsStream<TcpStream>`
+#[cfg(feature = "tls")]
+pub type GlacierStream = tokio_rustls::server::TlsStream<TcpStream>;
+
+/// 服务器与客户端之间的连接，开启 `tls` 时为 `TlsStream<TcpStream>`
+#[cfg(not(feature = "tls"))]
+pub type GlacierStream = TcpStream;
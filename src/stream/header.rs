@@ -0,0 +1,284 @@
+use std::str::from_utf8;
+
+use crate::prelude::{GlacierError, Kind, Result};
+
+/* ----------------------------------- HeaderMap ----------------------------------- */
+/// 请求头的只读视图, 直接引用 `OneRequest` 的缓冲区, 不会复制数据
+///
+/// 请求头名字不区分大小写, 值会去掉前后的空白
+/// # Examples
+/// ```
+/// let headers = req.headers();
+/// let host = headers.get("host");
+/// let accepts: Vec<&str> = headers.get_list("Accept").collect();
+/// let len: Option<ContentLength> = headers.typed();
+/// ```
+#[derive(Clone, Copy)]
+pub struct HeaderMap<'a> {
+    buf: &'a [u8],
+    headers_pos: &'a [[usize; 3]],
+}
+
+impl<'a> HeaderMap<'a> {
+    pub(crate) fn new(buf: &'a [u8], headers_pos: &'a [[usize; 3]]) -> Self {
+        HeaderMap { buf, headers_pos }
+    }
+
+    /// 测试用, 把 `raw` 中以 `\r\n` 结尾的每一行解析为请求头
+    #[cfg(test)]
+    pub(crate) fn with_raw(raw: &str, f: impl FnOnce(&HeaderMap)) {
+        let buf = bytes::BytesMut::from(raw);
+        let mut headers_pos = Vec::new();
+        let mut start = 0;
+        while let Some(pos) = raw[start..].find("\r\n") {
+            let line = [start, start + pos + 2];
+            headers_pos.push(crate::stream::request::RequestHeader::parse(&buf, line).unwrap());
+            start += pos + 2;
+        }
+        f(&HeaderMap::new(&buf, &headers_pos));
+    }
+
+    /// 请求头数量, 同名的请求头分别计算
+    pub fn len(&self) -> usize {
+        self.headers_pos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers_pos.is_empty()
+    }
+
+    /// 遍历所有请求头, 顺序与请求中的顺序一致,
+    /// 不是合法 utf8 的请求头会被跳过
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        let buf = self.buf;
+        self.headers_pos.iter().filter_map(move |header| {
+            let key = from_utf8(&buf[header[0]..header[1]]).ok()?;
+            let value = from_utf8(&buf[header[1] + 1..header[2] - 2]).ok()?;
+            Some((key, value.trim_matches([' ', '\t'])))
+        })
+    }
+
+    /// 查找第一个同名请求头
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.get_all(name).next()
+    }
+
+    /// 查找所有同名请求头, 例如多个 `Accept`、`Cache-Control`
+    pub fn get_all<'n>(&self, name: &'n str) -> impl Iterator<Item = &'a str> + 'n
+    where
+        'a: 'n,
+    {
+        self.iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// 按原始字节查找所有同名请求头, 用于 `Content-Length`、`Transfer-Encoding` 这样决定请求体长度的请求头,
+    /// 有一个值不是 ASCII 时返回错误, 而不是像 [`HeaderMap::get_all`] 一样跳过
+    pub(crate) fn get_all_ascii(&self, name: &str) -> Result<Vec<&'a str>> {
+        let buf = self.buf;
+        let mut values = Vec::new();
+        for header in self.headers_pos {
+            if !buf[header[0]..header[1]].eq_ignore_ascii_case(name.as_bytes()) {
+                continue;
+            }
+            let value = match from_utf8(&buf[header[1] + 1..header[2] - 2]) {
+                Ok(value) if value.is_ascii() => value,
+                _ => Err(GlacierError::not_ok_err(
+                    Kind::InRequest,
+                    format!("{} is not ascii", name),
+                ))?,
+            };
+            values.push(value.trim_matches([' ', '\t']));
+        }
+        Ok(values)
+    }
+
+    /// 查找所有同名请求头, 并按逗号拆分,
+    /// `Accept: a, b` 和两个 `Accept` 请求头结果一样
+    pub fn get_list<'n>(&self, name: &'n str) -> impl Iterator<Item = &'a str> + 'n
+    where
+        'a: 'n,
+    {
+        self.get_all(name)
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// 将请求头解析为实现了 [`Header`] 的类型
+    pub fn typed<H: Header>(&self) -> Option<H> {
+        let mut values = self.get_all(H::NAME).peekable();
+        values.peek()?;
+        H::decode(&mut values)
+    }
+}
+
+impl std::fmt::Debug for HeaderMap<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/* ----------------------------------- Header ----------------------------------- */
+/// 可以从请求头中解析出来的类型
+/// # Examples
+/// ```
+/// struct RequestId(String);
+///
+/// impl Header for RequestId {
+///     const NAME: &'static str = "X-Request-Id";
+///
+///     fn decode<'a>(values: &mut impl Iterator<Item = &'a str>) -> Option<Self> {
+///         values.next().map(|v| RequestId(String::from(v)))
+///     }
+/// }
+///
+/// let id: Option<RequestId> = req.headers().typed();
+/// ```
+pub trait Header: Sized {
+    /// 请求头名字
+    const NAME: &'static str;
+
+    /// 从所有同名请求头的值中解析, 至少有一个值
+    fn decode<'a>(values: &mut impl Iterator<Item = &'a str>) -> Option<Self>;
+}
+
+/// `Content-Length`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLength(pub u64);
+
+impl Header for ContentLength {
+    const NAME: &'static str = "Content-Length";
+
+    fn decode<'a>(values: &mut impl Iterator<Item = &'a str>) -> Option<Self> {
+        let mut len = None;
+        for value in values {
+            let value = value.parse().ok()?;
+            match len {
+                Some(len) if len != value => return None,
+                _ => len = Some(value),
+            }
+        }
+        len.map(ContentLength)
+    }
+}
+
+/// `Host`, 端口是可选的
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl Header for Host {
+    const NAME: &'static str = "Host";
+
+    fn decode<'a>(values: &mut impl Iterator<Item = &'a str>) -> Option<Self> {
+        let value = values.next()?;
+        // 多个 `Host` 是不合法的
+        if values.next().is_some() {
+            return None;
+        }
+
+        // `[::1]:8080` 这样的 ipv6 地址
+        let port_pos = match value.rfind(']') {
+            Some(end) => value[end..].find(':').map(|pos| end + pos),
+            None => value.rfind(':'),
+        };
+        let (host, port) = match port_pos {
+            Some(pos) => (&value[..pos], Some(value[pos + 1..].parse().ok()?)),
+            None => (value, None),
+        };
+        if !Host::is_valid(host) {
+            return None;
+        }
+
+        Some(Host {
+            host: String::from(host),
+            port,
+        })
+    }
+}
+
+impl Host {
+    /// 域名、ipv4 或者 `[...]` 形式的 ipv6 地址, 不接受 `@`、`/` 等会改变 url 含义的字符
+    fn is_valid(host: &str) -> bool {
+        match host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+        {
+            Some(ip) => {
+                !ip.is_empty()
+                    && ip
+                        .bytes()
+                        .all(|b| b.is_ascii_hexdigit() || b == b':' || b == b'.')
+            }
+            None => {
+                !host.is_empty()
+                    && host
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.' || b == b'_')
+            }
+        }
+    }
+}
+
+/// `User-Agent`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAgent(pub String);
+
+impl Header for UserAgent {
+    const NAME: &'static str = "User-Agent";
+
+    fn decode<'a>(values: &mut impl Iterator<Item = &'a str>) -> Option<Self> {
+        values.next().map(|value| UserAgent(String::from(value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_insensitive_and_multi_value() {
+        let raw = "host:  example.com:8080 \r\nAccept: text/html, application/json\r\nACCEPT: */*;q=0.1\r\nContent-Length: 3\r\n";
+        HeaderMap::with_raw(raw, |headers| {
+            assert_eq!(headers.len(), 4);
+            assert_eq!(headers.get("HOST"), Some("example.com:8080"));
+            assert_eq!(headers.get_all("accept").count(), 2);
+            assert_eq!(
+                headers.get_list("Accept").collect::<Vec<_>>(),
+                ["text/html", "application/json", "*/*;q=0.1"]
+            );
+            assert_eq!(headers.typed(), Some(ContentLength(3)));
+            assert_eq!(
+                headers.typed(),
+                Some(Host {
+                    host: String::from("example.com"),
+                    port: Some(8080)
+                })
+            );
+            assert_eq!(headers.typed::<UserAgent>(), None);
+        });
+    }
+
+    #[test]
+    fn reject_invalid_host() {
+        let decode = |value: &str| Host::decode(&mut [value].into_iter());
+        assert_eq!(
+            decode("[::1]:8080"),
+            Some(Host {
+                host: String::from("[::1]"),
+                port: Some(8080)
+            })
+        );
+        assert_eq!(decode("good.com@evil.com"), None);
+        assert_eq!(decode("evil.com/x"), None);
+        assert_eq!(decode(""), None);
+    }
+}
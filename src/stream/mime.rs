@@ -0,0 +1,102 @@
+use dashmap::DashMap;
+use std::{path::Path, sync::LazyLock};
+
+/// 未知类型
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// 默认的扩展名与 MIME 类型的对应关系, 文本类型都是 utf8
+const DEFAULT_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=UTF-8"),
+    ("htm", "text/html; charset=UTF-8"),
+    ("css", "text/css; charset=UTF-8"),
+    ("js", "text/javascript; charset=UTF-8"),
+    ("mjs", "text/javascript; charset=UTF-8"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("txt", "text/plain; charset=UTF-8"),
+    ("md", "text/markdown; charset=UTF-8"),
+    ("csv", "text/csv; charset=UTF-8"),
+    ("xml", "application/xml"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+
+/// 用户添加的类型, 优先于默认类型
+static CUSTOM_TYPES: LazyLock<DashMap<String, String>> = LazyLock::new(DashMap::new);
+
+/// 添加或替换扩展名对应的 MIME 类型, 扩展名不区分大小写, 不带 `.`
+pub(crate) fn register(ext: &str, mime: &str) {
+    let ext = ext.trim_start_matches('.').to_ascii_lowercase();
+    CUSTOM_TYPES.insert(ext, String::from(mime));
+}
+
+/// 根据扩展名查找 MIME 类型
+/// # Examples
+/// ```
+/// mime::from_ext("PNG") -> Some("image/png")
+/// ```
+pub fn from_ext(ext: &str) -> Option<String> {
+    let ext = ext.to_ascii_lowercase();
+    if let Some(mime) = CUSTOM_TYPES.get(&ext) {
+        return Some(mime.clone());
+    }
+
+    DEFAULT_TYPES
+        .iter()
+        .find(|(known, _)| *known == ext)
+        .map(|(_, mime)| String::from(*mime))
+}
+
+/// 根据文件路径的扩展名查找 MIME 类型, 未知类型返回 `application/octet-stream`
+/// # Examples
+/// ```
+/// mime::from_path("public/1.js") -> "text/javascript; charset=UTF-8"
+/// ```
+pub fn from_path(path: impl AsRef<Path>) -> String {
+    path.as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(from_ext)
+        .unwrap_or_else(|| String::from(OCTET_STREAM))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_and_register() {
+        assert_eq!(from_path("public/index.HTML"), "text/html; charset=UTF-8");
+        assert_eq!(from_path("public/app.wasm"), "application/wasm");
+        assert_eq!(from_path("public/noext"), OCTET_STREAM);
+
+        register(".glb", "model/gltf-binary");
+        register("svg", "image/svg+xml; charset=UTF-8");
+        assert_eq!(from_path("a/b.glb"), "model/gltf-binary");
+        assert_eq!(
+            from_ext("SVG").as_deref(),
+            Some("image/svg+xml; charset=UTF-8")
+        );
+    }
+}
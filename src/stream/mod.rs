@@ -1,3 +1,18 @@
+pub mod body;
+pub mod broadcast;
+pub mod date;
+pub mod extensions;
+pub mod file_cache;
 pub mod glacier_stream;
+pub mod header;
+pub mod mime;
+pub mod mount;
+pub mod multipart;
+pub mod negotiate;
+pub mod path;
 pub mod request;
-pub mod response;
\ No newline at end of file
+pub mod response;
+pub mod sse;
+pub mod status;
+#[cfg(feature = "watch")]
+pub mod watch;
@@ -0,0 +1,491 @@
+use bytes::Bytes;
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, sync::LazyLock};
+
+use crate::prelude::{Broadcast, GlacierError, Kind, Result, SlowConsumer, MOUNTS};
+use crate::stream::file_cache;
+
+/// 浏览器自动刷新使用的 SSE 路径
+pub(crate) const LIVE_RELOAD_PATH: &str = "/__glacier/live-reload";
+
+/// 注入到 HTML 中的脚本, 收到 `reload` 事件后刷新页面
+const LIVE_RELOAD_SCRIPT: &str = "<script>new EventSource(\"/__glacier/live-reload\")\
+    .addEventListener(\"reload\", () => location.reload());</script>";
+
+/// 文件改变时通知浏览器刷新
+pub(crate) static LIVE_RELOAD: LazyLock<Broadcast> =
+    LazyLock::new(|| Broadcast::new(4, SlowConsumer::Drop));
+
+/* ----------------------------------- Options ----------------------------------- */
+/// 静态目录挂载的配置
+/// # Examples
+/// ```
+/// let options = MountOptions::new()
+///     .cache_threshold(256 * 1024)
+///     .listing(true);
+///
+/// // 单页应用, 未知路径都发送 `index.html`
+/// let options = MountOptions::new().spa("index.html");
+/// ```
+#[derive(Debug, Clone)]
+pub struct MountOptions {
+    pub(crate) cache_threshold: u64,
+    pub(crate) hidden: bool,
+    pub(crate) index: Option<String>,
+    pub(crate) listing: bool,
+    pub(crate) spa: Option<String>,
+    #[cfg(feature = "watch")]
+    pub(crate) watch: bool,
+    pub(crate) live_reload: bool,
+}
+
+impl MountOptions {
+    /// 默认不超过 `1MB` 的文件放入缓存, 不发送以 `.` 开头的文件和目录,
+    /// 请求目录时发送 `index.html`, 不显示目录列表
+    pub fn new() -> Self {
+        MountOptions {
+            cache_threshold: 1024 * 1024,
+            hidden: false,
+            index: Some(String::from("index.html")),
+            listing: false,
+            spa: None,
+            #[cfg(feature = "watch")]
+            watch: false,
+            live_reload: false,
+        }
+    }
+
+    /// 放入缓存的文件的最大长度, 更大的文件每次都从磁盘流式发送
+    pub fn cache_threshold(mut self, size: u64) -> Self {
+        self.cache_threshold = size;
+        self
+    }
+
+    /// 是否发送以 `.` 开头的文件和目录
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// 请求目录时发送的文件, `None` 表示不发送
+    pub fn index(mut self, index: Option<&str>) -> Self {
+        self.index = index.map(String::from);
+        self
+    }
+
+    /// 目录中没有 index 文件时, 是否显示目录列表, 根据 `Accept` 返回 HTML 或 JSON
+    pub fn listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+
+    /// 单页应用模式, 挂载点下不存在的路径都发送 `entry`, 而不是404
+    /// # Args
+    /// - `entry` - 相对于挂载目录的文件路径, 例如 `index.html`
+    pub fn spa(mut self, entry: &str) -> Self {
+        self.spa = Some(String::from(entry.trim_start_matches('/')));
+        self
+    }
+
+    /// 监听目录中文件的变化, 更新或删除缓存, 不需要重启就能发送新的内容.
+    /// Linux 上使用 inotify, 不支持时轮询目录
+    #[cfg(feature = "watch")]
+    pub fn watch(mut self, watch: bool) -> Self {
+        self.watch = watch;
+        self
+    }
+
+    /// 开发时使用, 文件改变后自动刷新打开的页面, 同时开启 [`MountOptions::watch`].
+    /// 发送的 HTML 文件中会注入连接 `/__glacier/live-reload` 的脚本
+    #[cfg(feature = "watch")]
+    pub fn live_reload(mut self, live_reload: bool) -> Self {
+        self.live_reload = live_reload;
+        self.watch |= live_reload;
+        self
+    }
+}
+
+impl Default for MountOptions {
+    fn default() -> Self {
+        MountOptions::new()
+    }
+}
+
+/* ----------------------------------- Mount ----------------------------------- */
+/// 一个挂载点, 请求路径 `prefix` 之后的部分对应 `root` 中的文件
+#[derive(Debug, Clone)]
+pub struct Mount {
+    /// 请求路径前缀, 不以 `/` 结尾, 根路径为空字符串
+    pub(crate) prefix: String,
+    /// 文件夹路径, 不以 `/` 结尾
+    pub(crate) root: String,
+    pub(crate) options: MountOptions,
+}
+
+impl Mount {
+    /// # Args
+    /// - `prefix` - 请求路径前缀, 例如 `/static`
+    /// - `dir` - 文件夹路径, 例如 `./public`
+    pub(crate) fn new(prefix: &str, dir: &str, options: MountOptions) -> Result<Self> {
+        if !prefix.starts_with('/') {
+            Err(GlacierError::not_ok_err(
+                Kind::InServer,
+                format!("mount prefix must start with '/': {}", prefix),
+            ))?
+        }
+
+        let metadata = fs::metadata(dir)
+            .map_err(|e| GlacierError::not_ok_err(Kind::IOErr, format!("{}: {}", dir, e)))?;
+        if !metadata.is_dir() {
+            Err(GlacierError::not_ok_err(
+                Kind::IOErr,
+                format!("{}: not a directory", dir),
+            ))?
+        }
+
+        let root = dir.strip_prefix("./").unwrap_or(dir);
+        let root = match root.trim_end_matches('/') {
+            "" if root.starts_with('/') => "/",
+            "" => ".",
+            root => root,
+        };
+
+        Ok(Mount {
+            prefix: String::from(prefix.trim_end_matches('/')),
+            root: String::from(root),
+            options,
+        })
+    }
+
+    /// 请求路径在这个挂载点中的相对路径, 不匹配返回 `None`
+    fn relative<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let rest = path.strip_prefix(self.prefix.as_str())?;
+        match rest {
+            "" => Some(""),
+            _ => rest.strip_prefix('/'),
+        }
+    }
+
+    /// 相对路径对应的文件路径
+    pub(crate) fn file_path(&self, rel: &str) -> String {
+        let rel = rel.trim_end_matches('/');
+        match (self.root.as_str(), rel) {
+            (root, "") => String::from(root),
+            ("/", rel) => format!("/{}", rel),
+            (root, rel) => format!("{}/{}", root, rel),
+        }
+    }
+
+    /// 相对路径中是否有以 `.` 开头的部分
+    pub(crate) fn is_hidden(&self, rel: &str) -> bool {
+        !self.options.hidden && rel.split('/').any(|name| name.starts_with('.'))
+    }
+
+    async fn resolve(&self, path: &str, rel: &str) -> Target {
+        if self.is_hidden(rel) {
+            return Target::NotFound;
+        }
+
+        // 缓存中只有文件, 命中时不需要访问磁盘
+        let file_path = self.file_path(rel);
+        let live_reload = self.options.live_reload;
+        if let Some(file) = cached(&file_path, live_reload).filter(|_| !rel.ends_with('/')) {
+            return Target::File(file);
+        }
+
+        let metadata = tokio::fs::metadata(&file_path).await.ok();
+        let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+
+        if !is_dir && !rel.ends_with('/') {
+            if let Some(file) = self.open(&file_path).await {
+                return Target::File(file);
+            }
+        }
+
+        if is_dir && (self.options.index.is_some() || self.options.listing) {
+            if !path.ends_with('/') {
+                return Target::Redirect(format!("{}/", path));
+            }
+
+            if let Some(index) = &self.options.index {
+                if let Some(file) = self.open(&format!("{}/{}", file_path, index)).await {
+                    return Target::File(file);
+                }
+            }
+            if self.options.listing {
+                return Target::Listing(self.list(&file_path).await);
+            }
+        }
+
+        if let Some(entry) = &self.options.spa {
+            if let Some(file) = self.open(&self.file_path(entry)).await {
+                return Target::File(file);
+            }
+        }
+
+        Target::NotFound
+    }
+
+    /// 打开文件, 优先使用缓存, 不超过 `cache_threshold` 的文件读取后放入缓存
+    async fn open(&self, file_path: &str) -> Option<StaticFile> {
+        let live_reload = self.options.live_reload;
+        if let Some(file) = cached(file_path, live_reload) {
+            return Some(file);
+        }
+
+        let metadata = tokio::fs::metadata(file_path).await.ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        let etag = file_cache::etag(&metadata);
+        if metadata.len() > self.options.cache_threshold {
+            return Some(StaticFile {
+                path: String::from(file_path),
+                etag,
+                bytes: None,
+                live_reload,
+            });
+        }
+
+        let bytes = Bytes::from(tokio::fs::read(file_path).await.ok()?);
+        file_cache::lock().insert(file_path, bytes.clone(), etag.clone());
+        Some(StaticFile {
+            path: String::from(file_path),
+            etag,
+            bytes: Some(bytes),
+            live_reload,
+        })
+    }
+
+    /// 目录中的文件和子目录, 子目录在前, 按名称排序
+    async fn list(&self, dir: &str) -> Vec<Entry> {
+        let mut entries = BTreeMap::new();
+        let Ok(mut read_dir) = tokio::fs::read_dir(dir).await else {
+            return Vec::new();
+        };
+
+        while let Ok(Some(item)) = read_dir.next_entry().await {
+            let Some(name) = item.file_name().to_str().map(String::from) else {
+                continue;
+            };
+            if name.starts_with('.') && !self.options.hidden {
+                continue;
+            }
+            // 跟随符号链接
+            let Ok(metadata) = tokio::fs::metadata(item.path()).await else {
+                continue;
+            };
+            let entry = Entry {
+                name,
+                dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+            };
+            entries.insert((!entry.dir, entry.name.clone()), entry);
+        }
+
+        entries.into_values().collect()
+    }
+}
+
+fn cached(file_path: &str, live_reload: bool) -> Option<StaticFile> {
+    let (bytes, etag) = file_cache::lock().get(file_path)?;
+    Some(StaticFile {
+        path: String::from(file_path),
+        etag,
+        bytes: Some(bytes),
+        live_reload,
+    })
+}
+
+/* ----------------------------------- Resolve ----------------------------------- */
+/// 请求路径在挂载点中对应的内容
+#[derive(Debug, PartialEq)]
+pub(crate) enum Target {
+    File(StaticFile),
+    /// 请求目录时路径没有以 `/` 结尾, 重定向到带 `/` 的路径, 页面中的相对链接才正确
+    Redirect(String),
+    /// 目录列表
+    Listing(Vec<Entry>),
+    /// 浏览器自动刷新的 SSE 连接
+    LiveReload,
+    NotFound,
+}
+
+/// 要发送的静态文件
+#[derive(Debug, PartialEq)]
+pub(crate) struct StaticFile {
+    pub(crate) path: String,
+    pub(crate) etag: String,
+    /// 缓存中的内容, `None` 表示文件太大, 从磁盘流式发送
+    pub(crate) bytes: Option<Bytes>,
+    pub(crate) live_reload: bool,
+}
+
+impl StaticFile {
+    /// 在 HTML 的 `</body>` 之前注入自动刷新的脚本, 没有 `</body>` 时放在最后
+    pub(crate) fn inject_live_reload(bytes: &Bytes) -> Bytes {
+        let pos = bytes
+            .windows(7)
+            .rposition(|w| w.eq_ignore_ascii_case(b"</body>"))
+            .unwrap_or(bytes.len());
+
+        let mut html = Vec::with_capacity(bytes.len() + LIVE_RELOAD_SCRIPT.len());
+        html.extend_from_slice(&bytes[..pos]);
+        html.extend_from_slice(LIVE_RELOAD_SCRIPT.as_bytes());
+        html.extend_from_slice(&bytes[pos..]);
+        Bytes::from(html)
+    }
+}
+
+/// 目录列表中的一项
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Entry {
+    pub(crate) name: String,
+    pub(crate) dir: bool,
+    pub(crate) size: u64,
+}
+
+/// 查找请求路径对应的内容, 前缀最长的挂载点优先
+/// # Examples
+/// ```
+/// // 挂载 `/static` -> `./public`
+/// mount::resolve("/static/js/app.js").await -> Target::File(..) // public/js/app.js
+/// mount::resolve("/static").await -> Target::Redirect("/static/")
+/// ```
+pub(crate) async fn resolve(path: &str) -> Target {
+    let found = {
+        let mounts = MOUNTS.read().unwrap_or_else(|e| e.into_inner());
+        if path == LIVE_RELOAD_PATH && mounts.iter().any(|m| m.options.live_reload) {
+            return Target::LiveReload;
+        }
+        mounts
+            .iter()
+            .filter_map(|mount| Some((mount, mount.relative(path)?)))
+            .max_by_key(|(mount, _)| mount.prefix.len())
+            .map(|(mount, rel)| (mount.clone(), rel))
+    };
+
+    match found {
+        Some((mount, rel)) => mount.resolve(path, rel).await,
+        None => Target::NotFound,
+    }
+}
+
+/// 打开挂载目录中的文件, 文件不在任何挂载目录中时返回 `None`
+/// # Examples
+/// ```
+/// // 挂载 `/static` -> `./public`
+/// mount::open("public/404.html").await
+/// ```
+pub(crate) async fn open(file_path: &str) -> Option<StaticFile> {
+    let mount = {
+        let mounts = MOUNTS.read().unwrap_or_else(|e| e.into_inner());
+        mounts
+            .iter()
+            .find(|mount| {
+                let rel = match mount.root.as_str() {
+                    "/" => file_path.strip_prefix('/'),
+                    root => file_path
+                        .strip_prefix(root)
+                        .and_then(|p| p.strip_prefix('/')),
+                };
+                rel.is_some_and(|rel| {
+                    !mount.is_hidden(rel) && !rel.split('/').any(|name| name == "..")
+                })
+            })
+            .cloned()
+    }?;
+
+    mount.open(file_path).await
+}
+
+/// 添加挂载点, 相同前缀的挂载点会被替换
+pub(crate) fn register(mount: Mount) {
+    let mut mounts = MOUNTS.write().unwrap_or_else(|e| e.into_inner());
+    mounts.retain(|m| m.prefix != mount.prefix);
+    mounts.push(mount);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mount_and_resolve() {
+        let dir = std::env::temp_dir().join(format!("glacier-mount-{}", std::process::id()));
+        fs::create_dir_all(dir.join("css/deep")).unwrap();
+        fs::write(dir.join("index.html"), "index").unwrap();
+        fs::write(dir.join("css/deep/a.css"), "a").unwrap();
+        fs::write(dir.join("big.bin"), [0; 64]).unwrap();
+        fs::write(dir.join(".env"), "secret").unwrap();
+
+        let root = dir.to_string_lossy().to_string();
+        let options = MountOptions::new().cache_threshold(16);
+        register(Mount::new("/assets/", &root, options).unwrap());
+        assert!(Mount::new("assets", &root, MountOptions::new()).is_err());
+        assert!(Mount::new("/nope", "/nonexistent/glacier", MountOptions::new()).is_err());
+
+        let file = |target: Target| match target {
+            Target::File(file) => Some((file.path, file.bytes)),
+            _ => None,
+        };
+        let a = file(resolve("/assets/css/deep/a.css").await).unwrap();
+        assert_eq!(a.0, format!("{}/css/deep/a.css", root));
+        assert_eq!(a.1.as_deref(), Some(&b"a"[..]));
+        assert!(file_cache::lock().get(&a.0).is_some());
+        // 大文件不放入缓存
+        assert_eq!(file(resolve("/assets/big.bin").await).unwrap().1, None);
+        assert_eq!(resolve("/assets/.env").await, Target::NotFound);
+        assert_eq!(resolve("/assetsx/index.html").await, Target::NotFound);
+        assert!(open(&format!("{}/index.html", root)).await.is_some());
+        assert!(open(&format!("{}/.env", root)).await.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn index_listing_and_spa() {
+        let dir = std::env::temp_dir().join(format!("glacier-listing-{}", std::process::id()));
+        fs::create_dir_all(dir.join("css/deep")).unwrap();
+        fs::write(dir.join("index.html"), "index").unwrap();
+
+        let root = dir.to_string_lossy().to_string();
+        register(Mount::new("/listed/", &root, MountOptions::new()).unwrap());
+        let file = |target: Target| match target {
+            Target::File(file) => Some(file.path),
+            _ => None,
+        };
+
+        // 目录不以 `/` 结尾时重定向, 有 index 文件时发送 index 文件
+        assert_eq!(
+            resolve("/listed").await,
+            Target::Redirect(String::from("/listed/"))
+        );
+        let index = file(resolve("/listed/").await).unwrap();
+        assert_eq!(index, format!("{}/index.html", root));
+        assert_eq!(resolve("/listed/css/").await, Target::NotFound);
+
+        let options = MountOptions::new().listing(true).spa("index.html");
+        register(Mount::new("/listed", &root, options).unwrap());
+        assert_eq!(
+            resolve("/listed/css/").await,
+            Target::Listing(vec![Entry {
+                name: String::from("deep"),
+                dir: true,
+                size: 0
+            }])
+        );
+        let spa = file(resolve("/listed/app/settings").await).unwrap();
+        assert_eq!(spa, format!("{}/index.html", root));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn inject_live_reload() {
+        let html = Bytes::from_static(b"<html><BODY>x</BODY></html>");
+        let html = StaticFile::inject_live_reload(&html);
+        assert!(html.starts_with(b"<html><BODY>x<script>"));
+        assert!(html.ends_with(b"</script></BODY></html>"));
+    }
+}
@@ -0,0 +1,630 @@
+use bytes::{Buf, Bytes, BytesMut};
+use futures::StreamExt;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::io::AsyncWriteExt;
+
+use crate::prelude::{GlacierError, Kind, Result};
+use crate::stream::body::BodyStream;
+
+/// 分段头的最大长度
+const MAX_PART_HEAD_LEN: usize = 16 * 1024;
+
+/* ----------------------------------- Multipart ----------------------------------- */
+#[derive(Debug, PartialEq)]
+enum State {
+    /// 第一个分隔符之前的内容
+    Preamble,
+    /// 刚读完分隔符, 接下来是 `--` 或者 `\r\n`
+    Delimiter,
+    /// 当前分段的数据
+    Data,
+    End,
+}
+
+/// `multipart/form-data` 解析器, 由 `OneRequest::multipart` 创建,
+/// 逐个读取分段, 分段的数据也是按块读取的
+/// # Examples
+/// ```
+/// let mut multipart = req.multipart()?;
+/// while let Some(mut field) = multipart.next_field().await? {
+///     println!("{:?} {:?}", field.name(), field.file_name());
+///     while let Some(chunk) = field.chunk().await? {
+///         file.write_all(&chunk).await?;
+///     }
+/// }
+/// ```
+pub struct Multipart<'a> {
+    body: BodyStream<'a>,
+    buf: BytesMut,
+    /// `\r\n--boundary`
+    delimiter: Vec<u8>,
+    state: State,
+}
+
+impl<'a> Multipart<'a> {
+    pub(crate) fn new(body: BodyStream<'a>, boundary: &str) -> Self {
+        // 第一个分隔符前面可能没有 `\r\n`, 补上之后统一处理
+        let mut buf = BytesMut::with_capacity(8 * 1024);
+        buf.extend_from_slice(b"\r\n");
+
+        Multipart {
+            body,
+            buf,
+            delimiter: [b"\r\n--", boundary.as_bytes()].concat(),
+            state: State::Preamble,
+        }
+    }
+
+    /// 从 `Content-Type` 中取出 `boundary`
+    pub(crate) fn boundary(content_type: &str) -> Result<&str> {
+        let mut params = content_type.split(';');
+        let mime = params.next().unwrap_or("").trim();
+        if !mime.eq_ignore_ascii_case("multipart/form-data") {
+            Err(GlacierError::not_ok_err(
+                Kind::UnsupportedMediaType,
+                format!("unexpected Content-Type: {}", content_type),
+            ))?
+        }
+
+        let boundary = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+            .map(|(_, value)| value.trim().trim_matches('"'));
+        match boundary {
+            Some(boundary) if (1..=70).contains(&boundary.len()) => Ok(boundary),
+            _ => Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "missing multipart boundary",
+            )),
+        }
+    }
+
+    /// 继续读取请求体, 请求体已经读完则返回错误
+    async fn fill(&mut self) -> Result<()> {
+        match self.body.next().await {
+            Some(data) => {
+                self.buf.extend_from_slice(&data?);
+                Ok(())
+            }
+            None => Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "unexpected end of multipart body",
+            )),
+        }
+    }
+
+    fn find(&self, needle: &[u8]) -> Option<usize> {
+        self.buf.windows(needle.len()).position(|w| w == needle)
+    }
+
+    /// 读取下一个分段, 没有更多分段时返回 `None`,
+    /// 上一个分段没有读完的数据会被丢弃
+    pub async fn next_field(&mut self) -> Result<Option<Field<'_, 'a>>> {
+        loop {
+            match self.state {
+                State::Preamble => match self.find(&self.delimiter) {
+                    Some(pos) => {
+                        self.buf.advance(pos + self.delimiter.len());
+                        self.state = State::Delimiter;
+                    }
+                    None => {
+                        // 保留末尾可能是分隔符一部分的数据
+                        let keep = self.delimiter.len() - 1;
+                        if self.buf.len() > keep {
+                            self.buf.advance(self.buf.len() - keep);
+                        }
+                        self.fill().await?;
+                    }
+                },
+                State::Data => while self.data_chunk().await?.is_some() {},
+                State::Delimiter => {
+                    let padding = self
+                        .buf
+                        .iter()
+                        .take_while(|b| **b == b' ' || **b == b'\t')
+                        .count();
+                    if self.buf.len() < padding + 2 {
+                        self.fill().await?;
+                        continue;
+                    }
+
+                    if self.buf.starts_with(b"--") {
+                        // 丢弃结束分隔符之后的内容, 让连接可以继续使用
+                        while let Some(data) = self.body.next().await {
+                            data?;
+                        }
+                        self.buf.clear();
+                        self.state = State::End;
+                        continue;
+                    }
+                    if &self.buf[padding..padding + 2] != b"\r\n" {
+                        Err(GlacierError::not_ok_err(
+                            Kind::InRequest,
+                            "invalid multipart delimiter",
+                        ))?
+                    }
+                    self.buf.advance(padding + 2);
+
+                    // 没有头的分段直接是一个空行
+                    let head = match self.buf.starts_with(b"\r\n") {
+                        true => self.buf.split_to(2),
+                        false => loop {
+                            match self.find(b"\r\n\r\n") {
+                                Some(pos) => break self.buf.split_to(pos + 4),
+                                None if self.buf.len() > MAX_PART_HEAD_LEN => {
+                                    Err(GlacierError::not_ok_err(
+                                        Kind::InRequest,
+                                        "multipart headers too large",
+                                    ))?
+                                }
+                                None => self.fill().await?,
+                            }
+                        },
+                    };
+                    let head = &head[..head.len().saturating_sub(4)];
+                    let field = FieldInfo::parse(head)?;
+
+                    self.state = State::Data;
+                    return Ok(Some(Field {
+                        multipart: self,
+                        info: field,
+                    }));
+                }
+                State::End => return Ok(None),
+            }
+        }
+    }
+
+    /// 读取当前分段的下一块数据, 读到分隔符时返回 `None`
+    async fn data_chunk(&mut self) -> Result<Option<Bytes>> {
+        if self.state != State::Data {
+            return Ok(None);
+        }
+
+        loop {
+            match self.find(&self.delimiter) {
+                Some(0) => {
+                    self.buf.advance(self.delimiter.len());
+                    self.state = State::Delimiter;
+                    return Ok(None);
+                }
+                Some(pos) => return Ok(Some(self.buf.split_to(pos).freeze())),
+                None => {
+                    // 分隔符可能被拆分在两次读取之间, 末尾的数据先不返回
+                    let keep = self.delimiter.len() - 1;
+                    if self.buf.len() > keep {
+                        let len = self.buf.len() - keep;
+                        return Ok(Some(self.buf.split_to(len).freeze()));
+                    }
+                    self.fill().await?;
+                }
+            }
+        }
+    }
+
+    /// 只读取文本分段并解析为 `T`, 文件分段会被跳过, 与 `get_params` 一样使用 `serde_qs`
+    /// # Args
+    /// - `max_size` - 所有文本分段的最大总长度, 超过返回 `Kind::PayloadTooLarge`
+    /// # Examples
+    /// ```
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Profile {
+    ///     name: String,
+    ///     age: u8,
+    /// }
+    ///
+    /// let profile: Profile = req.multipart()?.fields(64 * 1024).await?;
+    ///
+    /// ```
+    pub async fn fields<T: for<'b> Deserialize<'b>>(&mut self, max_size: u64) -> Result<T> {
+        let mut fields = Vec::new();
+        let mut total = 0;
+        while let Some(mut field) = self.next_field().await? {
+            if field.file_name().is_some() {
+                continue;
+            }
+            let name = field.name().unwrap_or_default().to_string();
+            let value = field.text(max_size.saturating_sub(total)).await?;
+            total += value.len() as u64;
+            fields.push((name, value));
+        }
+
+        parse_fields(&fields)
+    }
+
+    /// 读取所有分段, 文件保存到临时目录, 文本分段放在内存中
+    /// # Examples
+    /// ```
+    /// let options = SpoolOptions::new("/tmp/uploads")
+    ///     .max_file_size(10 * 1024 * 1024)
+    ///     .max_total_size(50 * 1024 * 1024);
+    /// let form = req.multipart()?.spool(&options).await?;
+    ///
+    /// for file in &form.files {
+    ///     println!("{:?} -> {:?}", file.file_name, file.path);
+    /// }
+    /// let profile: Profile = form.parse()?;
+    /// ```
+    pub async fn spool(&mut self, options: &SpoolOptions) -> Result<MultipartForm> {
+        let mut form = MultipartForm::default();
+        match self.spool_into(options, &mut form).await {
+            Ok(()) => Ok(form),
+            Err(e) => {
+                // 出错时删除已经保存的文件
+                for file in &form.files {
+                    let _ = tokio::fs::remove_file(&file.path).await;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn spool_into(&mut self, options: &SpoolOptions, form: &mut MultipartForm) -> Result<()> {
+        tokio::fs::create_dir_all(&options.dir).await?;
+
+        let too_large = |what: &str| {
+            GlacierError::not_ok_err(Kind::PayloadTooLarge, format!("{} too large", what))
+        };
+        let mut total = 0;
+        while let Some(mut field) = self.next_field().await? {
+            let name = field.name().unwrap_or_default().to_string();
+
+            if field.file_name().is_none() {
+                let max_size = options
+                    .max_field_size
+                    .min(options.max_total_size.saturating_sub(total));
+                let value = field.text(max_size).await?;
+                total += value.len() as u64;
+                form.fields.push((name, value));
+                continue;
+            }
+
+            let path = options.dir.join(temp_file_name());
+            let mut file = tokio::fs::File::create(&path).await?;
+            form.files.push(SavedFile {
+                name,
+                file_name: field.file_name().map(String::from),
+                content_type: field.content_type().map(String::from),
+                path,
+                size: 0,
+            });
+
+            let mut size = 0;
+            while let Some(chunk) = field.chunk().await? {
+                size += chunk.len() as u64;
+                total += chunk.len() as u64;
+                if size > options.max_file_size {
+                    Err(too_large("file"))?
+                }
+                if total > options.max_total_size {
+                    Err(too_large("multipart body"))?
+                }
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+
+            if let Some(saved) = form.files.last_mut() {
+                saved.size = size;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/* ----------------------------------- Field ----------------------------------- */
+#[derive(Debug, Default)]
+struct FieldInfo {
+    name: Option<String>,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+impl FieldInfo {
+    fn parse(head: &[u8]) -> Result<FieldInfo> {
+        let head = std::str::from_utf8(head)?;
+        let mut info = FieldInfo::default();
+
+        for line in head.split("\r\n") {
+            let (key, value) = line.split_once(':').ok_or_else(|| {
+                GlacierError::not_ok_err(Kind::InRequest, "invalid multipart header")
+            })?;
+            let value = value.trim();
+
+            if key.trim().eq_ignore_ascii_case("Content-Type") {
+                info.content_type = Some(String::from(value));
+            } else if key.trim().eq_ignore_ascii_case("Content-Disposition") {
+                let mut file_name_ext = None;
+                for (key, value) in disposition_params(value) {
+                    match key.to_ascii_lowercase().as_str() {
+                        "name" => info.name = Some(value),
+                        "filename" => info.file_name = Some(value),
+                        "filename*" => file_name_ext = decode_ext_value(&value),
+                        _ => {}
+                    }
+                }
+                // `filename*` 优先
+                if file_name_ext.is_some() {
+                    info.file_name = file_name_ext;
+                }
+            }
+        }
+
+        Ok(info)
+    }
+}
+
+/// 一个 `multipart/form-data` 分段
+pub struct Field<'m, 'a> {
+    multipart: &'m mut Multipart<'a>,
+    info: FieldInfo,
+}
+
+impl Field<'_, '_> {
+    /// 表单字段名
+    pub fn name(&self) -> Option<&str> {
+        self.info.name.as_deref()
+    }
+
+    /// 上传文件的文件名, 支持 `filename*=UTF-8''...`
+    pub fn file_name(&self) -> Option<&str> {
+        self.info.file_name.as_deref()
+    }
+
+    /// 分段的 `Content-Type`
+    pub fn content_type(&self) -> Option<&str> {
+        self.info.content_type.as_deref()
+    }
+
+    /// 读取下一块数据, 读完后返回 `None`
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        self.multipart.data_chunk().await
+    }
+
+    /// 读取全部数据
+    /// # Args
+    /// - `max_size` - 最大长度, 超过返回 `Kind::PayloadTooLarge`
+    pub async fn bytes(&mut self, max_size: u64) -> Result<BytesMut> {
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            if (buf.len() + chunk.len()) as u64 > max_size {
+                Err(GlacierError::not_ok_err(
+                    Kind::PayloadTooLarge,
+                    "multipart field too large",
+                ))?
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf)
+    }
+
+    /// 以 utf8 文本读取全部数据
+    /// # Args
+    /// - `max_size` - 最大长度, 超过返回 `Kind::PayloadTooLarge`
+    pub async fn text(&mut self, max_size: u64) -> Result<String> {
+        let buf = self.bytes(max_size).await?;
+        Ok(String::from(std::str::from_utf8(&buf)?))
+    }
+}
+
+/* ----------------------------------- Spool ----------------------------------- */
+/// `Multipart::spool` 的配置
+#[derive(Debug, Clone)]
+pub struct SpoolOptions {
+    dir: PathBuf,
+    max_file_size: u64,
+    max_total_size: u64,
+    max_field_size: u64,
+}
+
+impl SpoolOptions {
+    /// 创建配置, 文件保存在 `dir` 中, 默认单个文件最大 `10MB`,
+    /// 总大小最大 `100MB`, 单个文本分段最大 `64KB`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SpoolOptions {
+            dir: dir.into(),
+            max_file_size: 10 * 1024 * 1024,
+            max_total_size: 100 * 1024 * 1024,
+            max_field_size: 64 * 1024,
+        }
+    }
+
+    /// 单个文件的最大长度
+    pub fn max_file_size(mut self, size: u64) -> Self {
+        self.max_file_size = size;
+        self
+    }
+
+    /// 所有分段的最大总长度
+    pub fn max_total_size(mut self, size: u64) -> Self {
+        self.max_total_size = size;
+        self
+    }
+
+    /// 单个文本分段的最大长度
+    pub fn max_field_size(mut self, size: u64) -> Self {
+        self.max_field_size = size;
+        self
+    }
+}
+
+/// 保存到临时目录的文件, 使用完需要自行删除或移动
+#[derive(Debug)]
+pub struct SavedFile {
+    pub name: String,
+    pub file_name: Option<String>,
+    pub content_type: Option<String>,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// `Multipart::spool` 的结果
+#[derive(Debug, Default)]
+pub struct MultipartForm {
+    pub fields: Vec<(String, String)>,
+    pub files: Vec<SavedFile>,
+}
+
+impl MultipartForm {
+    /// 将文本分段解析为 `T`
+    pub fn parse<T: for<'a> Deserialize<'a>>(&self) -> Result<T> {
+        parse_fields(&self.fields)
+    }
+}
+
+/* ----------------------------------- 工具函数 ----------------------------------- */
+/// 表单字段名中的 `[`、`]` 不编码, 以支持 `serde_qs` 的嵌套结构
+const QS_KEY: &AsciiSet = &NON_ALPHANUMERIC.remove(b'[').remove(b']');
+
+fn parse_fields<T: for<'a> Deserialize<'a>>(fields: &[(String, String)]) -> Result<T> {
+    let query = fields
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "{}={}",
+                utf8_percent_encode(key, QS_KEY),
+                utf8_percent_encode(value, NON_ALPHANUMERIC)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    serde_qs::from_str(&query)
+        .map_err(|e| GlacierError::not_ok_err(Kind::InRequest, format!("invalid form: {}", e)))
+}
+
+/// 解析 `form-data; name="a"; filename="b.txt"` 中的参数
+fn disposition_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = match value.split_once(';') {
+        Some((_, rest)) => rest,
+        None => return params,
+    };
+
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(';').trim();
+        let value = value.trim_start();
+
+        let (value, next) = match value.strip_prefix('"') {
+            // 带引号的值, 处理 `\"` 转义
+            Some(quoted) => {
+                let mut out = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => out.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => out.push(c),
+                    }
+                }
+                (out, &quoted[end..])
+            }
+            None => match value.split_once(';') {
+                Some((value, next)) => (String::from(value.trim()), next),
+                None => (String::from(value.trim()), ""),
+            },
+        };
+
+        params.push((String::from(key), value));
+        rest = match next.split_once(';') {
+            Some((_, next)) => next,
+            None => "",
+        };
+    }
+
+    params
+}
+
+/// 解码 RFC 8187 的扩展参数 `UTF-8'en'%E4%BD%A0.txt`
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+
+    let bytes: Vec<u8> = percent_decode_str(encoded).collect();
+    match charset.to_ascii_lowercase().as_str() {
+        "utf-8" => String::from_utf8(bytes).ok(),
+        "iso-8859-1" => Some(bytes.into_iter().map(char::from).collect()),
+        _ => None,
+    }
+}
+
+/// 临时文件名, 进程内唯一
+fn temp_file_name() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!(
+        "glacier-{}-{}-{}.upload",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multipart(parts: &[&'static [u8]]) -> Multipart<'static> {
+        let chunks: Vec<Result<Bytes>> = parts.iter().map(|p| Ok(Bytes::from_static(p))).collect();
+        Multipart::new(Box::pin(futures::stream::iter(chunks)), "XyZ")
+    }
+
+    #[tokio::test]
+    async fn boundary_split_across_reads() {
+        let mut multipart = multipart(&[
+            b"--XyZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nhel",
+            b"lo\r\n--X",
+            b"yZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"; ",
+            b"filename*=UTF-8''%E4%BD%A0%E5%A5%BD.txt\r\nContent-Type: text/plain\r\n\r\n",
+            b"line1\r\nline2\r",
+            b"\n--XyZ--\r\n",
+        ]);
+
+        let mut field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("title"));
+        assert_eq!(field.file_name(), None);
+        assert_eq!(field.text(1024).await.unwrap(), "hello");
+
+        let mut field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        assert_eq!(field.file_name(), Some("你好.txt"));
+        assert_eq!(field.content_type(), Some("text/plain"));
+        assert_eq!(&field.bytes(1024).await.unwrap()[..], b"line1\r\nline2");
+
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn collect_fields() {
+        #[derive(Deserialize)]
+        struct Profile {
+            name: String,
+            age: u8,
+        }
+
+        let mut multipart = multipart(&[
+            b"preamble\r\n--XyZ\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nA & B\r\n",
+            b"--XyZ\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"a.png\"\r\n\r\n",
+            b"\x89PNG\r\n--XyZ\r\nContent-Disposition: form-data; name=\"age\"\r\n\r\n18\r\n--XyZ--",
+        ]);
+        let profile: Profile = multipart.fields(1024).await.unwrap();
+        assert_eq!(profile.name, "A & B");
+        assert_eq!(profile.age, 18);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::stream::header::HeaderMap;
+
+/// 解析 `Accept` 一类请求头中的一项, 返回值和权重 `q`,
+/// 格式错误的项返回 `None`, 会被忽略
+/// # Examples
+/// ```
+/// "text/html;level=1;q=0.5" -> ("text/html", 0.5)
+/// "gzip" -> ("gzip", 1.0)
+/// ```
+fn parse_item(item: &str) -> Option<(&str, f32)> {
+    let mut parts = item.split(';').map(str::trim);
+    let value = parts.next().filter(|value| !value.is_empty())?;
+
+    let mut q = 1.0;
+    for param in parts {
+        if let Some((key, value)) = param.split_once('=') {
+            if key.trim().eq_ignore_ascii_case("q") {
+                q = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|q| (0.0..=1.0).contains(q))?;
+            }
+        }
+    }
+
+    Some((value, q))
+}
+
+/// 从 `offered` 中选出权重最高的一个, 权重相同时选择靠前的
+/// # Args
+/// - `specificity` - 客户端的一项能否匹配服务器提供的值, 以及匹配的具体程度,
+///   多项都能匹配时以最具体的一项为准
+/// - `unmatched` - 没有任何一项匹配时的权重
+fn best<'o>(
+    headers: &HeaderMap,
+    name: &str,
+    offered: &[&'o str],
+    specificity: impl Fn(&str, &str) -> Option<usize>,
+    unmatched: impl Fn(&str) -> f32,
+) -> Option<&'o str> {
+    // 没有这个请求头表示都可以接受
+    if !headers.contains(name) {
+        return offered.first().copied();
+    }
+
+    let ranges: Vec<(&str, f32)> = headers.get_list(name).filter_map(parse_item).collect();
+    let mut best: Option<(&'o str, f32)> = None;
+    for &offer in offered {
+        let mut matched: Option<(usize, f32)> = None;
+        for &(range, q) in &ranges {
+            if let Some(level) = specificity(range, offer) {
+                if matched.is_none_or(|(best_level, _)| level > best_level) {
+                    matched = Some((level, q));
+                }
+            }
+        }
+
+        let q = matched.map_or_else(|| unmatched(offer), |(_, q)| q);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((offer, q));
+        }
+    }
+
+    best.map(|(offer, _)| offer)
+}
+
+/// 根据 `Accept` 选择媒体类型, 匹配时忽略 `q` 以外的参数
+pub(crate) fn media_type<'o>(headers: &HeaderMap, offered: &[&'o str]) -> Option<&'o str> {
+    best(
+        headers,
+        "Accept",
+        offered,
+        |range, offer| {
+            let offer = offer.split(';').next().unwrap_or(offer).trim();
+            let (range_type, range_sub) = range.split_once('/')?;
+            let (offer_type, _) = offer.split_once('/')?;
+
+            match (range_type, range_sub) {
+                ("*", "*") => Some(0),
+                (_, "*") if range_type.eq_ignore_ascii_case(offer_type) => Some(1),
+                _ if range.eq_ignore_ascii_case(offer) => Some(2),
+                _ => None,
+            }
+        },
+        |_| 0.0,
+    )
+}
+
+/// 根据 `Accept-Language` 选择语言, `en` 匹配 `en` 和 `en-US`, 更长的前缀更具体
+pub(crate) fn language<'o>(headers: &HeaderMap, offered: &[&'o str]) -> Option<&'o str> {
+    best(
+        headers,
+        "Accept-Language",
+        offered,
+        |range, offer| {
+            if range == "*" {
+                return Some(0);
+            }
+
+            let prefix = offer.get(..range.len())?;
+            let rest = &offer[range.len()..];
+            match prefix.eq_ignore_ascii_case(range) && (rest.is_empty() || rest.starts_with('-')) {
+                true => Some(range.len()),
+                false => None,
+            }
+        },
+        |_| 0.0,
+    )
+}
+
+/// 根据 `Accept-Encoding` 选择压缩方式
+pub(crate) fn encoding<'o>(headers: &HeaderMap, offered: &[&'o str]) -> Option<&'o str> {
+    best(
+        headers,
+        "Accept-Encoding",
+        offered,
+        |range, offer| match range {
+            "*" => Some(0),
+            _ if range.eq_ignore_ascii_case(offer) => Some(1),
+            _ => None,
+        },
+        |offer| match offer.eq_ignore_ascii_case("identity") {
+            true => 1.0,
+            false => 0.0,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_q_values_and_wildcards() {
+        let raw = "Accept: text/html;q=0.5, application/*;q=0.8, */*;q=0.1\r\n\
+                   Accept-Language: en;q=0.5, zh-CN\r\n\
+                   Accept-Encoding: gzip;q=0.5, br, *;q=0\r\n";
+        HeaderMap::with_raw(raw, |headers| {
+            let offered = ["text/html", "application/json"];
+            assert_eq!(media_type(headers, &offered), Some("application/json"));
+            assert_eq!(media_type(headers, &["image/png"]), Some("image/png"));
+
+            assert_eq!(language(headers, &["en-US", "zh-CN"]), Some("zh-CN"));
+            assert_eq!(language(headers, &["en-US", "fr"]), Some("en-US"));
+            assert_eq!(language(headers, &["zh", "fr"]), None);
+
+            assert_eq!(encoding(headers, &["gzip", "br"]), Some("br"));
+            assert_eq!(encoding(headers, &["deflate", "identity"]), None);
+        });
+
+        HeaderMap::with_raw("Accept: text/*;q=0\r\n", |headers| {
+            assert_eq!(media_type(headers, &["text/plain"]), None);
+            assert_eq!(encoding(headers, &["gzip", "identity"]), Some("gzip"));
+        });
+    }
+}
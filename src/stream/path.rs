@@ -0,0 +1,143 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::borrow::Cow;
+
+use crate::prelude::{GlacierError, Kind, Result};
+
+/// 重新编码路径时需要编码的字符
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// 编码 `Location` 中的 url 时需要编码的字符, 不包括 `%`、`?`、`#` 等有特殊含义的字符
+const LOCATION: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// 解码并规范化请求路径, 不包含查询参数
+///
+/// - 百分号解码, 解码后不是合法 utf8、包含 `\0` 或者 `/` (`%2F`) 时返回错误
+/// - 去掉 `.` 和 `..`
+/// - 合并连续的 `/`
+/// - 保留末尾的 `/`
+/// # Examples
+/// ```
+/// "/hello%20world" -> "/hello world"
+/// "//hello" -> "/hello"
+/// "/a/../hello/" -> "/hello/"
+/// ```
+pub(crate) fn normalize(path: &str) -> Result<Cow<'_, str>> {
+    if !path.starts_with('/') {
+        Err(GlacierError::not_ok_err(
+            Kind::InRequest,
+            format!("invalid request path: {}", path),
+        ))?
+    }
+
+    // 大部分路径不需要处理
+    if !path.contains('%') && !path.contains("//") && !path.contains("/.") {
+        return Ok(Cow::Borrowed(path));
+    }
+
+    let mut segments: Vec<Cow<str>> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path[1..].split('/') {
+        let segment = percent_decode_str(segment).decode_utf8().map_err(|_| {
+            GlacierError::not_ok_err(Kind::InRequest, "request path is not valid utf8")
+        })?;
+        if segment.contains(['\0', '/']) {
+            Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "encoded NUL or slash in request path",
+            ))?
+        }
+
+        trailing_slash = true;
+        match segment.as_ref() {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => {
+                segments.push(segment);
+                trailing_slash = false;
+            }
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len());
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if trailing_slash || normalized.is_empty() {
+        normalized.push('/');
+    }
+
+    Ok(Cow::Owned(normalized))
+}
+
+/// 将规范化后的路径重新编码, 用于 `Location` 等响应头
+pub(crate) fn encode(path: &str) -> Cow<'_, str> {
+    utf8_percent_encode(path, PATH).into()
+}
+
+/// 编码重定向的目标地址, 已经编码过的部分保持不变,
+/// 换行等控制字符也会被编码, 不会破坏响应头
+/// # Examples
+/// ```
+/// "/你好?a=b c" -> "/%E4%BD%A0%E5%A5%BD?a=b%20c"
+/// "https://example.com/a%20b" -> "https://example.com/a%20b"
+/// ```
+pub(crate) fn encode_location(url: &str) -> Cow<'_, str> {
+    utf8_percent_encode(url, LOCATION).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_paths() {
+        let cases = [
+            ("/", "/"),
+            ("/hello", "/hello"),
+            ("/hello%20world", "/hello world"),
+            ("//hello", "/hello"),
+            ("/a/../hello", "/hello"),
+            ("/a/./b/", "/a/b/"),
+            ("/a/b/..", "/a/"),
+            ("/../../x", "/x"),
+            ("/%2e%2e/x", "/x"),
+            ("/%E4%BD%A0%E5%A5%BD", "/你好"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(normalize(path).unwrap(), expected, "{}", path);
+        }
+
+        assert!(normalize("/a%2Fb").is_err());
+        assert!(normalize("/a%00").is_err());
+        assert!(normalize("/%FF").is_err());
+        assert!(normalize("hello").is_err());
+        assert_eq!(encode("/hello world/你"), "/hello%20world/%E4%BD%A0");
+        assert_eq!(
+            encode_location("https://a.com/x%20y?q=1 2\r\n#top"),
+            "https://a.com/x%20y?q=1%202%0D%0A#top"
+        );
+    }
+}
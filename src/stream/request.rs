@@ -1,54 +1,101 @@
-use bytes::BytesMut;
-use serde::Deserialize;
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::str::from_utf8_unchecked;
-use std::{io::IoSlice, net::IpAddr};
-use tokio::{io::AsyncWriteExt, net::TcpStream};
+use std::{io::IoSlice, net::IpAddr, sync::Arc};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{timeout, Instant};
 
-use crate::prelude::{GlacierError, Response, Result, FILES_BUF};
+use crate::config::GlacierConfig;
+use crate::prelude::{
+    ContentType, GlacierError, Kind, Response, ResponseBuilder, Result, StatusCode,
+};
+use crate::stream::body::{BodyKind, BodyReader, BodyStream};
+use crate::stream::extensions::Extensions;
+use crate::stream::glacier_stream::GlacierStream;
+use crate::stream::header::HeaderMap;
+use crate::stream::mount::{Entry, StaticFile, Target, LIVE_RELOAD};
+use crate::stream::multipart::Multipart;
+use crate::stream::response::{Body, Trailers};
+use crate::stream::sse::SseReceiver;
+use crate::stream::{mime, mount, negotiate, path};
+
+/// 处理函数没有读取的请求体, 最多丢弃这么多, 超过则关闭连接
+const MAX_DRAIN_LEN: usize = 1024 * 1024;
 
 // /* ------------------------------ // OneRequest ----------------------------- */
 pub struct ReqInfo {
     pub(crate) line_pos: [usize; 4],
     pub(crate) headers_pos: Vec<[usize; 3]>,
+    /// 请求头结束的位置, 即请求体开始的位置
+    pub(crate) body_pos: usize,
+    /// 请求头是否完全来自上一个请求剩下的数据, 即流水线中排队的请求
+    pub(crate) pipelined: bool,
 }
 
 pub struct OneRequest {
-    #[cfg(feature = "tls")]
-    pub(crate) stream: tokio_rustls::server::TlsStream<TcpStream>,
-
-    #[cfg(not(feature = "tls"))]
-    pub(crate) stream: TcpStream,
+    pub(crate) stream: GlacierStream,
 
     pub(crate) addr: IpAddr,
     pub(crate) buf: BytesMut,
     pub(crate) line_pos: [usize; 4],
     pub(crate) headers_pos: Vec<[usize; 3]>,
+
+    /// 请求头之后已经读取、但还没有解码的数据
+    pub(crate) rest: BytesMut,
+    pub(crate) body_kind: BodyKind,
+    /// `body()` 读取到的完整请求体
+    pub(crate) body: BytesMut,
+    pub(crate) trailers: Vec<(String, String)>,
+    /// 请求体的最大长度, 以及已经读取的长度
+    pub(crate) body_limit: Option<u64>,
+    pub(crate) body_read: u64,
+    /// 解码、规范化之后的路径, 与原始路径相同时为 `None`
+    pub(crate) route_path: Option<String>,
+
+    pub(crate) config: Arc<GlacierConfig>,
+    /// 响应后是否保持连接, 以及这个连接还能处理的请求数量
+    pub(crate) keep_alive: bool,
+    pub(crate) remaining_requests: usize,
+    /// 客户端发送了 `Expect: 100-continue`, 还没有回复 `100 Continue`
+    expect_continue: bool,
+    /// 已经发送了最终响应 (不包括 `1xx`)
+    responded: bool,
+    pub(crate) extensions: Extensions,
+    /// 内容协商用到的请求头, 响应时加到 `Vary` 中
+    vary: Vec<&'static str>,
 }
 
 impl OneRequest {
-    #[cfg(not(feature = "tls"))]
-    pub fn new(stream: TcpStream, buf: BytesMut, req_info: ReqInfo, addr: IpAddr) -> OneRequest {
-        OneRequest {
-            stream,
-            addr,
-            buf,
-            line_pos: req_info.line_pos,
-            headers_pos: req_info.headers_pos,
-        }
-    }
-    #[cfg(feature = "tls")]
     pub fn new(
-        stream: tokio_rustls::server::TlsStream<TcpStream>,
-        buf: BytesMut,
+        stream: GlacierStream,
+        mut buf: BytesMut,
         req_info: ReqInfo,
         addr: IpAddr,
+        config: Arc<GlacierConfig>,
     ) -> OneRequest {
+        let rest = buf.split_off(req_info.body_pos);
         OneRequest {
             stream,
             addr,
             buf,
             line_pos: req_info.line_pos,
             headers_pos: req_info.headers_pos,
+            rest,
+            body_kind: BodyKind::Done,
+            body: BytesMut::new(),
+            trailers: Vec::new(),
+            body_limit: config.body_limit,
+            body_read: 0,
+            route_path: None,
+            config,
+            keep_alive: true,
+            remaining_requests: 0,
+            expect_continue: false,
+            responded: false,
+            extensions: Extensions::new(),
+            vary: Vec::new(),
         }
     }
 
@@ -71,14 +118,20 @@ impl OneRequest {
         unsafe { from_utf8_unchecked(method) }
     }
 
-    /// 解析请求路径用于路由函数
+    /// 解析请求路径用于路由函数, 路径已经过百分号解码和规范化
     /// # Examples
     /// ```
     /// "/public/index.html" -> "/public/index.html"
     /// "/public" -> "/public"
     /// "/public?a=1&b=2" -> "/public"
+    /// "/hello%20world" -> "/hello world"
+    /// "//a/../public" -> "/public"
     /// ```
     pub fn path_for_routes(&self) -> &str {
+        if let Some(route_path) = &self.route_path {
+            return route_path;
+        }
+
         let uri = unsafe {
             self.buf
                 .get_unchecked(self.line_pos[1]..self.line_pos[2] - 1)
@@ -91,6 +144,25 @@ impl OneRequest {
         }
     }
 
+    /// 解码、规范化请求路径, 见 [`path::normalize`]
+    pub(crate) fn normalize_path(&mut self) -> Result<()> {
+        self.route_path = match path::normalize(self.path_for_routes())? {
+            Cow::Owned(route_path) => Some(route_path),
+            Cow::Borrowed(_) => None,
+        };
+        Ok(())
+    }
+
+    /// 查询参数, 不包含 `?`
+    /// # Examples
+    /// ```
+    /// "/public?a=1&b=2" -> Some("a=1&b=2")
+    /// "/public" -> None
+    /// ```
+    pub fn query(&self) -> Option<&str> {
+        self.path().split_once('?').map(|(_, query)| query)
+    }
+
     /// 请求路径
     pub fn path(&self) -> &str {
         let uri = unsafe {
@@ -109,26 +181,30 @@ impl OneRequest {
         unsafe { from_utf8_unchecked(version) }
     }
 
-    /// 查找请求头
+    /// 请求头, 名字不区分大小写
+    /// # Examples
+    /// ```
+    /// let host = req.headers().get("host");
+    /// for accept in req.headers().get_list("Accept") {
+    ///     println!("{}", accept);
+    /// }
+    /// ```
+    pub fn headers(&self) -> HeaderMap<'_> {
+        HeaderMap::new(&self.buf, &self.headers_pos)
+    }
+
+    /// 查找请求头, 名字不区分大小写, 有多个同名请求头时返回第一个
     /// Examples
     /// ```
     /// let header_value = req.query_header("Host").unwrap();
     /// ```
     pub fn query_header(&self, query_key: &str) -> Option<&str> {
-        let query_key = query_key.as_bytes();
-        let headers = &self.headers_pos;
-        for header in headers {
-            let key = unsafe { self.buf.get_unchecked(header[0]..header[1]) };
-            if query_key == key {
-                unsafe {
-                    let value = self.buf.get_unchecked(header[1] + 2..header[2] - 2);
-                    let value = from_utf8_unchecked(value);
-                    return Some(value);
-                }
-            }
-        }
+        self.headers().get(query_key)
+    }
 
-        None
+    /// 客户端重连时带回的最后一个事件 id, 见 [`OneRequest::respond_sse`]
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.headers().get("Last-Event-ID")
     }
 
     /// 获取请求参数
@@ -159,37 +235,590 @@ impl OneRequest {
         }
     }
 
-    #[cfg(not(feature = "tls"))]
-    /// 获取请求体
-    pub async fn body(&mut self) -> Option<&[u8]> {
-        if self.method() == "GET" {
-            return None;
+    /// 从 `offered` 中选出客户端最想要的类型, 根据 `Accept` 的 `q` 值和通配符判断,
+    /// 没有 `Accept` 时返回第一个, 都不接受时返回 `None`.
+    /// 响应会自动加上 `Vary: Accept`
+    /// # Examples
+    /// ```
+    /// // Accept: text/html;q=0.9, application/*
+    /// match req.accepts(&["text/html", "application/json"]) {
+    ///     Some("application/json") => {}
+    ///     Some(_) => {}
+    ///     None => {} // 406
+    /// }
+    /// ```
+    pub fn accepts<'o>(&mut self, offered: &[&'o str]) -> Option<&'o str> {
+        self.add_vary("Accept");
+        negotiate::media_type(&self.headers(), offered)
+    }
+
+    /// 根据 `Accept-Language` 选择语言, `en` 可以匹配 `en-US`
+    /// # Examples
+    /// ```
+    /// let lang = req.accepts_language(&["zh-CN", "en"]).unwrap_or("en");
+    /// ```
+    pub fn accepts_language<'o>(&mut self, offered: &[&'o str]) -> Option<&'o str> {
+        self.add_vary("Accept-Language");
+        negotiate::language(&self.headers(), offered)
+    }
+
+    /// 根据 `Accept-Encoding` 选择压缩方式, 没有被明确拒绝时 `identity` 总是可以接受
+    /// # Examples
+    /// ```
+    /// let encoding = req.accepts_encoding(&["br", "gzip", "identity"]);
+    /// ```
+    pub fn accepts_encoding<'o>(&mut self, offered: &[&'o str]) -> Option<&'o str> {
+        self.add_vary("Accept-Encoding");
+        negotiate::encoding(&self.headers(), offered)
+    }
+
+    fn add_vary(&mut self, name: &'static str) {
+        if !self.vary.contains(&name) {
+            self.vary.push(name);
         }
-        while let Ok(_len @ 1..) = self.stream.try_read_buf(&mut self.buf) {}
+    }
 
-        let pos_1 = self.headers_pos[self.headers_pos.len() - 1][2];
+    /// 请求的扩展数据, 由中间件写入
+    /// # Examples
+    /// ```
+    /// let request_id = req.extensions().get::<RequestId>();
+    /// ```
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
 
-        unsafe { Some(self.buf.get_unchecked(pos_1 + 2..)) }
+    /// 写入扩展数据, 传递给之后的中间件和处理函数
+    /// # Examples
+    /// ```
+    /// async fn auth_middle(mut req: OneRequest) -> Result<OneRequest> {
+    ///     let user = check_token(req.query_header("Authorization"))?;
+    ///     req.extensions_mut().insert(user);
+    ///     Ok(req)
+    /// }
+    /// ```
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// 根据 `Content-Length` 和 `Transfer-Encoding` 确定请求体的长度,
+    /// 两者同时出现时返回错误, 防止请求走私
+    pub(crate) fn parse_body_kind(&mut self) -> Result<()> {
+        // 不是合法 utf8 的请求头会被 `get_all` 跳过, 这里按原始字节查找, 防止被当成没有请求体
+        let headers = self.headers();
+        let body_kind = BodyKind::parse(
+            headers.get_all_ascii("Content-Length")?.into_iter(),
+            headers.get_all_ascii("Transfer-Encoding")?.into_iter(),
+        )?;
+
+        // HTTP/1.0 的客户端不会等待 `100 Continue`
+        let expect_continue = !body_kind.is_done()
+            && self.version() != "HTTP/1.0"
+            && headers
+                .get_all_ascii("Expect")?
+                .first()
+                .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"));
+
+        self.body_kind = body_kind;
+        self.expect_continue = expect_continue;
+        Ok(())
     }
 
-    /// 发生响应
+    /// 限制请求体的最大长度, `Content-Length` 超过限制时直接返回413,
+    /// `Transfer-Encoding: chunked` 的请求体则在读取超过限制时返回413
     /// # Examples
     /// ```
-    /// let res = ResponseBuilder::new()
+    /// // 等同于 #[glacier(POST, "/upload", limit = "10MB")]
+    /// req.limit_body(10 * 1024 * 1024).await?;
+    /// ```
+    pub async fn limit_body(&mut self, limit: u64) -> Result<()> {
+        self.body_limit = Some(limit);
+        self.check_body_limit().await
+    }
+
+    /// `Content-Length` 超过限制时返回413
+    pub(crate) async fn check_body_limit(&mut self) -> Result<()> {
+        match (&self.body_kind, self.body_limit) {
+            (BodyKind::Length(len), Some(limit)) if self.body_read + len > limit => {
+                self.reject_too_large().await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 发送413响应, 并返回 `Kind::PayloadTooLarge`
+    async fn reject_too_large(&mut self) -> Result<()> {
+        tracing::debug!(limit = self.body_limit, "payload too large");
+        self.body_kind = BodyKind::Done;
+        self.keep_alive = false;
+
+        let res = ResponseBuilder::new(0)
+            .status(StatusCode::PAYLOAD_TOO_LARGE)
+            .body(b"Payload Too Large")
+            .build();
+        self.respond(res).await?;
+
+        Err(GlacierError::OkErr(Kind::PayloadTooLarge))
+    }
+
+    /// 读取下一段请求体, 读完后返回 `None`,
+    /// `Transfer-Encoding: chunked` 的请求体会被解码
+    pub(crate) async fn body_chunk(&mut self) -> Result<Option<Bytes>> {
+        if self.body_read == 0 {
+            self.check_body_limit().await?;
+        }
+
+        loop {
+            if let Some(data) = self.body_kind.decode(&mut self.rest, &mut self.trailers)? {
+                self.body_read += data.len() as u64;
+                if let Some(limit) = self.body_limit {
+                    if self.body_read > limit {
+                        self.reject_too_large().await?;
+                    }
+                }
+                return Ok(Some(data));
+            }
+            if self.body_kind.is_done() {
+                return Ok(None);
+            }
+
+            self.read_rest().await?;
+        }
+    }
+
+    /// 从连接中读取更多数据到 `rest`,
+    /// 第一次读取前回复 `100 Continue`, 客户端收到后才会发送请求体
+    async fn read_rest(&mut self) -> Result<()> {
+        if self.expect_continue {
+            self.expect_continue = false;
+            self.stream
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .await?;
+            self.stream.flush().await?;
+        }
+
+        self.rest.reserve(4096);
+        let read_task = self.stream.read_buf(&mut self.rest);
+        match timeout(self.config.body_timeout, read_task).await {
+            Ok(Ok(0)) => Err(GlacierError::OkErr(Kind::EofErr))?,
+            Ok(Ok(_len @ 1..)) => {}
+            Ok(Err(e)) => Err(e)?,
+            Err(_) => Err(GlacierError::OkErr(Kind::TimeOutErr))?,
+        }
+
+        Ok(())
+    }
+
+    /// 丢弃处理函数没有读取的请求体, 返回连接和已经读取到的下一个请求的数据,
+    /// 剩余的请求体太大时返回错误, 由调用者关闭连接
+    pub(crate) async fn finish(mut self) -> Result<(GlacierStream, BytesMut)> {
+        let mut drained = 0;
+        while !self.body_kind.is_done() {
+            match self.body_kind.decode(&mut self.rest, &mut self.trailers)? {
+                Some(data) => drained += data.len(),
+                None if self.body_kind.is_done() => break,
+                None => self.read_rest().await?,
+            }
+
+            if drained > MAX_DRAIN_LEN {
+                Err(GlacierError::not_ok_err(
+                    Kind::InRequest,
+                    "unread request body too large",
+                ))?
+            }
+        }
+
+        Ok((self.stream, self.rest))
+    }
+
+    /// 获取请求体, 支持 `Content-Length` 和 `Transfer-Encoding: chunked`
+    /// # Examples
+    /// ```
+    /// let body = req.body().await?;
+    /// println!("{:#?}", std::str::from_utf8(body));
+    /// ```
+    pub async fn body(&mut self) -> Result<&[u8]> {
+        while let Some(data) = self.body_chunk().await? {
+            self.body.extend_from_slice(&data);
+        }
+
+        Ok(&self.body)
+    }
+
+    /// 以流的方式读取请求体, 不会把整个请求体放进内存, 适合大文件上传,
+    /// 读完后连接可以继续处理下一个请求
+    /// # Examples
+    /// ```
+    /// use futures::StreamExt;
+    ///
+    /// let mut body = req.body_stream();
+    /// while let Some(chunk) = body.next().await {
+    ///     file.write_all(&chunk?).await?;
+    /// }
+    /// ```
+    pub fn body_stream(&mut self) -> BodyStream<'_> {
+        let stream = futures::stream::unfold(Some(self), |req| async move {
+            let req = req?;
+            match req.body_chunk().await {
+                Ok(Some(data)) => Some((Ok(data), Some(req))),
+                Ok(None) => None,
+                // 出错之后不再继续读取
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+        Box::pin(stream)
+    }
+
+    /// 以 `AsyncRead` 的方式读取请求体
+    /// # Examples
+    /// ```
+    /// let mut file = tokio::fs::File::create("upload.bin").await?;
+    /// tokio::io::copy(&mut req.body_reader(), &mut file).await?;
+    /// ```
+    pub fn body_reader(&mut self) -> BodyReader<'_> {
+        BodyReader::new(self.body_stream())
+    }
+
+    /// 检查 `Content-Type` 是否符合要求, 不符合则返回 `Kind::UnsupportedMediaType`
+    fn expect_content_type(&self, expected: fn(&str) -> bool) -> Result<()> {
+        let content_type = self.query_header("Content-Type").unwrap_or("");
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+
+        match expected(&mime.to_ascii_lowercase()) {
+            true => Ok(()),
+            false => Err(GlacierError::not_ok_err(
+                Kind::UnsupportedMediaType,
+                format!("unexpected Content-Type: {}", content_type),
+            )),
+        }
+    }
+
+    /// 将 `application/json` 请求体解析为 `T`
+    ///
+    /// `Content-Type` 不对时返回 `Kind::UnsupportedMediaType`(415),
+    /// 解析失败时返回 `Kind::InRequest`(400)
+    /// # Examples
+    /// ```
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct User {
+    ///     name: String,
+    ///     age: u8,
+    /// }
+    ///
+    /// let user: User = req.json().await?;
+    ///
+    /// ```
+    pub async fn json<T: for<'a> Deserialize<'a>>(&mut self) -> Result<T> {
+        self.expect_content_type(|mime| {
+            mime == "application/json"
+                || (mime.starts_with("application/") && mime.ends_with("+json"))
+        })?;
+
+        let body = self.body().await?;
+        serde_json::from_slice(body)
+            .map_err(|e| GlacierError::not_ok_err(Kind::InRequest, format!("invalid json: {}", e)))
+    }
+
+    /// 将 `application/x-www-form-urlencoded` 请求体解析为 `T`,
+    /// 与 `get_params` 一样使用 `serde_qs`
+    ///
+    /// `Content-Type` 不对时返回 `Kind::UnsupportedMediaType`(415),
+    /// 解析失败时返回 `Kind::InRequest`(400)
+    /// # Examples
+    /// ```
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Login {
+    ///     username: String,
+    ///     password: String,
+    /// }
+    ///
+    /// let login: Login = req.form().await?;
+    ///
+    /// ```
+    pub async fn form<T: for<'a> Deserialize<'a>>(&mut self) -> Result<T> {
+        self.expect_content_type(|mime| mime == "application/x-www-form-urlencoded")?;
+
+        let body = self.body().await?;
+        serde_qs::from_bytes(body)
+            .map_err(|e| GlacierError::not_ok_err(Kind::InRequest, format!("invalid form: {}", e)))
+    }
+
+    /// 以流的方式解析 `multipart/form-data` 请求体
+    ///
+    /// `Content-Type` 不对时返回 `Kind::UnsupportedMediaType`(415)
+    /// # Examples
+    /// ```
+    /// let mut multipart = req.multipart()?;
+    /// while let Some(mut field) = multipart.next_field().await? {
+    ///     if let Some(file_name) = field.file_name() {
+    ///         let mut file = tokio::fs::File::create(file_name).await?;
+    ///         while let Some(chunk) = field.chunk().await? {
+    ///             file.write_all(&chunk).await?;
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn multipart(&mut self) -> Result<Multipart<'_>> {
+        let content_type = self.query_header("Content-Type").unwrap_or("");
+        let boundary = String::from(Multipart::boundary(content_type)?);
+
+        Ok(Multipart::new(self.body_stream(), &boundary))
+    }
+
+    /// 获取 `Transfer-Encoding: chunked` 请求体末尾的 trailer, 需要先读完请求体
+    pub fn trailers(&self) -> &[(String, String)] {
+        &self.trailers
+    }
+
+    /// 查找 trailer, 不区分大小写
+    /// # Examples
+    /// ```
+    /// req.body().await?;
+    /// let checksum = req.query_trailer("Checksum");
+    /// ```
+    pub fn query_trailer(&self, query_key: &str) -> Option<&str> {
+        self.trailers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(query_key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// 发送响应, 由服务器补充 `Content-Length` 或 `Transfer-Encoding`、`Connection` 和 `Vary`,
+    /// `HEAD` 请求只发送响应头
+    /// # Examples
+    /// ```
+    /// let res = ResponseBuilder::new(128)
     ///     .status(200)
-    ///     .header("Keep-Alive", "close")
-    ///     .body("Hello, World!")
+    ///     .header("Connection", "close")
+    ///     .body(b"Hello, World!")
     ///     .build();
     /// req.respond(res).await.unwrap();
     /// ```
     pub async fn respond(&mut self, mut res: Response) -> Result<()> {
-        self.stream.write_all_buf(&mut res.buf).await?;
+        self.check_responded()?;
+        // 响应中已经有 `Connection` 时以响应为准
+        if let Some(connection) = res.headers.get("Connection") {
+            if connection.to_ascii_lowercase().contains("close") {
+                self.keep_alive = false;
+            }
+        }
+        // 内容协商用到的请求头
+        if !self.vary.is_empty() && !res.headers.contains("Vary") {
+            res.headers.insert("Vary", self.vary.join(", "));
+        }
+
+        /* ---------------------------- // 确定响应体的长度 ---------------------------- */
+        if !res.allows_body() {
+            res.body = Body::Empty;
+        }
+        let mut file = None;
+        let mut chunked = false;
+        match &res.body {
+            Body::Empty if res.allows_body() => res.headers.insert("Content-Length", "0"),
+            Body::Empty => {}
+            Body::Bytes(bytes) => res
+                .headers
+                .insert("Content-Length", bytes.len().to_string()),
+            Body::File(path) => {
+                if !res.headers.contains("Content-Type") {
+                    res.headers.insert("Content-Type", mime::from_path(path));
+                }
+                let f = tokio::fs::File::open(path).await?;
+                let len = f.metadata().await?.len();
+                res.headers.insert("Content-Length", len.to_string());
+                file = Some(f.take(len));
+            }
+            Body::Stream(_) if res.headers.contains("Content-Length") => {}
+            // HTTP/1.0 不支持 chunked, 以关闭连接表示响应体结束
+            Body::Stream(_) if self.version() == "HTTP/1.0" => self.keep_alive = false,
+            Body::Stream(_) => {
+                res.headers.insert("Transfer-Encoding", "chunked");
+                chunked = true;
+            }
+        }
+
+        self.fill_headers(&mut res);
+
+        /* ---------------------------- // 发送响应头和响应体 ---------------------------- */
+        if !res.status.is_informational() {
+            self.responded = true;
+        }
+        let head = res.head();
+        let body = match self.method() {
+            "HEAD" => Body::Empty,
+            _ => res.body,
+        };
+        match body {
+            Body::Empty => write_all_vectored(&mut self.stream, &[&head]).await?,
+            Body::Bytes(bytes) => write_all_vectored(&mut self.stream, &[&head, &bytes]).await?,
+            Body::File(_) => {
+                self.stream.write_all(&head).await?;
+                if let Some(mut file) = file {
+                    tokio::io::copy(&mut file, &mut self.stream).await?;
+                }
+            }
+            Body::Stream(mut stream) => {
+                self.stream.write_all(&head).await?;
+                while let Some(chunk) = stream.next().await {
+                    let chunk = match chunk {
+                        Ok(chunk) => chunk,
+                        Err(e) => {
+                            // 响应已经发送了一部分, 只能关闭连接
+                            self.keep_alive = false;
+                            Err(e)?
+                        }
+                    };
+                    if chunk.is_empty() {
+                        continue;
+                    }
+
+                    match chunked {
+                        true => {
+                            let size = format!("{:x}\r\n", chunk.len());
+                            let bufs = [size.as_bytes(), &chunk, b"\r\n"];
+                            write_all_vectored(&mut self.stream, &bufs).await?;
+                        }
+                        false => self.stream.write_all(&chunk).await?,
+                    }
+                }
+                if chunked {
+                    let mut last = String::from("0\r\n");
+                    for (key, value) in res.trailers.iter().flat_map(Trailers::take) {
+                        last.push_str(&format!("{}: {}\r\n", key, value));
+                    }
+                    last.push_str("\r\n");
+                    self.stream.write_all(last.as_bytes()).await?;
+                }
+            }
+        }
         self.stream.flush().await?;
 
         Ok(())
     }
 
-    /// 发送放在缓存中的静态资源
+    /// 是否已经发送了响应, 例如读取请求体超过限制时已经回复了413
+    pub fn responded(&self) -> bool {
+        self.responded
+    }
+
+    /// 一个请求只能有一个最终响应
+    fn check_responded(&self) -> Result<()> {
+        match self.responded {
+            true => Err(GlacierError::not_ok_err(
+                Kind::InServer,
+                "response already sent",
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// 补充每个响应都有的 `Date`、`Server`、`Connection` 和 `Keep-Alive`, 已经设置的不会覆盖
+    fn fill_headers(&self, res: &mut Response) {
+        res.fill_date_and_server(self.config.server.as_deref());
+        if !res.headers.contains("Connection") {
+            match self.keep_alive() {
+                true => {
+                    res.headers.insert("Connection", "keep-alive");
+                    res.headers.insert("Keep-Alive", self.keep_alive_params());
+                }
+                false => res.headers.insert("Connection", "close"),
+            }
+        }
+    }
+
+    /// 发送 JSON 响应, 序列化失败时发送500.
+    /// 查询参数中有 `pretty` (且不是 `pretty=0`、`pretty=false`) 时带缩进
+    /// # Examples
+    /// ```
+    /// // GET /users/1?pretty
+    /// req.respond_json(&user).await?;
+    /// ```
+    pub async fn respond_json<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let pretty = self.query().is_some_and(|query| {
+            query.split('&').any(|pair| match pair.split_once('=') {
+                Some((key, flag)) => key == "pretty" && flag != "0" && flag != "false",
+                None => pair == "pretty",
+            })
+        });
+
+        let builder = ResponseBuilder::new(0);
+        let res = match pretty {
+            true => builder.json_pretty(value),
+            false => builder.json(value),
+        };
+        self.respond(res.build()).await
+    }
+
+    /// 以 `text/event-stream` 持续推送事件, 直到所有 `SseSender` 被丢弃或者客户端断开,
+    /// 没有事件时定时发送注释保持连接. 推送结束后关闭连接
+    /// # Examples
+    /// ```
+    /// #[glacier(GET, "/events")]
+    /// async fn events(mut req: OneRequest) {
+    ///     let start = req.last_event_id().and_then(|id| id.parse().ok()).unwrap_or(0);
+    ///     let (tx, events) = sse::channel(16);
+    ///     tokio::spawn(async move {
+    ///         for n in start.. {
+    ///             let event = Event::new().id(n.to_string()).data("tick");
+    ///             if tx.send(event).await.is_err() {
+    ///                 break; // 客户端已经断开
+    ///             }
+    ///             tokio::time::sleep(Duration::from_secs(1)).await;
+    ///         }
+    ///     });
+    ///     req.respond_sse(events).await?;
+    /// }
+    /// ```
+    pub async fn respond_sse(&mut self, events: SseReceiver) -> Result<()> {
+        let SseReceiver {
+            mut rx,
+            ping_interval,
+        } = events;
+        self.check_responded()?;
+
+        // 响应体以关闭连接结束
+        self.keep_alive = false;
+        let mut res = ResponseBuilder::new(0)
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .header("X-Accel-Buffering", "no")
+            .build();
+        self.fill_headers(&mut res);
+        self.responded = true;
+        self.stream.write_all(&res.head()).await?;
+        self.stream.flush().await?;
+        if self.method() == "HEAD" {
+            return Ok(());
+        }
+
+        let mut ping = tokio::time::interval_at(Instant::now() + ping_interval, ping_interval);
+        let mut probe = [0; 64];
+        loop {
+            let data = tokio::select! {
+                event = rx.recv() => match event {
+                    Some(event) => event.to_bytes(),
+                    None => break,
+                },
+                _ = ping.tick() => Bytes::from_static(b": ping\n\n"),
+                // 客户端不会再发送数据, 读到 EOF 说明已经断开
+                read = self.stream.read(&mut probe) => match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => continue,
+                },
+            };
+
+            if self.stream.write_all(&data).await.is_err() || self.stream.flush().await.is_err() {
+                break;
+            }
+            ping.reset();
+        }
+
+        tracing::debug!("sse stream closed");
+        Ok(())
+    }
+
+    /// 发送挂载目录中的静态资源, 经过静态资源缓存, 文件不存在时返回错误
     /// # Examples
     /// ```
     /// if let Err(e) = req.respond_buf("public/index.html").await {
@@ -197,65 +826,233 @@ impl OneRequest {
     /// }
     /// ```
     pub async fn respond_buf(&mut self, file_path: String) -> Result<()> {
-        // 获取缓存中的文件内容
-        let buf = match FILES_BUF.get(&file_path) {
-            Some(buf) => buf,
+        match mount::open(&file_path).await {
+            Some(file) => self.respond_file(file).await,
             None => {
                 tracing::info!(file_path, "new req to file that not exist");
                 Err(GlacierError::Option)?
             }
+        }
+    }
+
+    /// 发送挂载目录中的静态资源, 根据挂载配置发送目录的 index 文件、目录列表或者单页应用的入口文件,
+    /// 没有匹配的挂载点或文件不存在时发送404
+    /// # Examples
+    /// ```
+    /// // 挂载 `/static` -> `./public`, 请求 `/static/js/app.js` 发送 `public/js/app.js`
+    /// req.respond_static().await?;
+    /// ```
+    pub async fn respond_static(&mut self) -> Result<()> {
+        match mount::resolve(self.path_for_routes()).await {
+            Target::File(file) => self.respond_file(file).await,
+            Target::Redirect(location) => self.respond_308(&location).await,
+            Target::Listing(entries) => self.respond_listing(&entries).await,
+            Target::LiveReload => {
+                let events = LIVE_RELOAD.subscribe_sse("reload");
+                self.respond_sse(events).await
+            }
+            Target::NotFound => self.respond_404().await,
+        }
+    }
+
+    /// 发送挂载目录中的文件, 带有 `ETag`, 与 `If-None-Match` 相同时发送304
+    async fn respond_file(&mut self, file: StaticFile) -> Result<()> {
+        let not_modified = self.headers().get("If-None-Match").is_some_and(|tags| {
+            tags.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == file.etag.trim_start_matches("W/")
+            })
+        });
+        if not_modified {
+            let mut res = Response::new(StatusCode::NOT_MODIFIED);
+            res.headers.insert("ETag", file.etag);
+            return self.respond(res).await;
+        }
+
+        let content_type = mime::from_path(&file.path);
+        let builder = ResponseBuilder::new(0)
+            .header("ETag", &file.etag)
+            .header("Content-Type", &content_type);
+        let mut res = builder.build();
+        res.body = match file.bytes {
+            Some(bytes) if file.live_reload && content_type.starts_with("text/html") => {
+                Body::Bytes(StaticFile::inject_live_reload(&bytes))
+            }
+            Some(bytes) => Body::Bytes(bytes),
+            // 大文件从磁盘流式发送
+            None => Body::File(file.path.into()),
         };
+        self.respond(res).await
+    }
 
-        let header = format!(
-            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: keep-alive\r\n\r\n",
-            buf.len()
-        );
+    /// 发送目录列表, 客户端接受 JSON 时发送 JSON, 否则发送 HTML
+    async fn respond_listing(&mut self, entries: &[Entry]) -> Result<()> {
+        if self.accepts(&["text/html", "application/json"]) == Some("application/json") {
+            return self.respond_json(entries).await;
+        }
 
-        let bufs = [IoSlice::new(header.as_bytes()), IoSlice::new(&buf)];
-        let writer = &mut self.stream;
-        writer.write_vectored(&bufs).await?;
-        writer.flush().await?;
+        let escape = |text: &str| {
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+        let title = escape(self.path_for_routes());
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n\
+             <body>\n<h1>{0}</h1>\n<ul>\n<li><a href=\"../\">../</a></li>\n",
+            title
+        );
+        for entry in entries {
+            let name = match entry.dir {
+                true => format!("{}/", entry.name),
+                false => entry.name.clone(),
+            };
+            html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                escape(&path::encode(&name)),
+                escape(&name)
+            ));
+        }
+        html.push_str("</ul>\n</body>\n</html>\n");
 
-        Ok(())
+        let res = ResponseBuilder::new(0)
+            .content_type(ContentType::Html)
+            .body(html.as_bytes())
+            .build();
+        self.respond(res).await
     }
 
     /// 发送默认响应：`Hello, world!`
     pub async fn respond_hello(&mut self) -> Result<()> {
-        let res =
-            "HTTP/1.1 200 OK\r\nContent-Length: 13\r\nConnection: keep-alive\r\n\r\nHello, world!";
-        self.stream.write_all(res.as_bytes()).await?;
-        self.stream.flush().await?;
-
-        Ok(())
+        let res = ResponseBuilder::new(0).body(b"Hello, world!").build();
+        self.respond(res).await
     }
 
     /// 发送404响应，先从缓存中查找是否存在 `public/404.html`，
     /// 不存在则返回字符串：`404 Not Found`
     pub async fn respond_404(&mut self) -> Result<()> {
-        let file_buf = FILES_BUF.get("public/404.html");
-
-        if let Some(file_buf) = file_buf.as_deref() {
-            let header = format!(
-                "HTTP/1.1 404 Not Found\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
-                file_buf.len()
-            );
-
-            // 使用缓冲写入合并 header 和 body
-            let writer = &mut self.stream;
-            let bufs = [IoSlice::new(header.as_bytes()), IoSlice::new(file_buf)];
-            writer.write_vectored(&bufs).await?;
-            writer.flush().await?;
-        } else {
-            let res =
-            "HTTP/1.1 404 Not Found\r\nContent-Length: 13\r\nConnection: close\r\n\r\n404 Not Found";
-            self.stream.write_all(res.as_bytes()).await?;
-            self.stream.flush().await?;
+        let file_buf = mount::open("public/404.html")
+            .await
+            .and_then(|file| file.bytes);
+        let (body, content_type) = match file_buf {
+            Some(file_buf) => (file_buf, ContentType::Html),
+            None => (Bytes::from_static(b"404 Not Found"), ContentType::Plain),
+        };
+
+        let mut res = ResponseBuilder::new(0)
+            .status(StatusCode::NOT_FOUND)
+            .content_type(content_type)
+            .build();
+        res.body = Body::Bytes(body);
+        self.respond(res).await
+    }
+
+    /// 根据错误发送对应的响应代码, 见 [`GlacierError::status_code`],
+    /// 请求体可能没有读完, 响应后关闭连接.
+    /// 已经发送过响应时 (例如请求体超过限制时已经回复了413) 什么也不做
+    /// # Examples
+    /// ```
+    /// if let Err(e) = req.form::<Login>().await {
+    ///     req.respond_error(&e).await?;
+    /// }
+    /// ```
+    pub async fn respond_error(&mut self, e: &GlacierError) -> Result<()> {
+        if self.responded {
+            return Ok(());
         }
 
-        Ok(())
+        let status = e.status_code();
+        let reason = status.reason().unwrap_or_default();
+
+        self.keep_alive = false;
+        let res = ResponseBuilder::new(0)
+            .status(status)
+            .body(reason.as_bytes())
+            .build();
+        self.respond(res).await
+    }
+
+    /// 发送308重定向, 路径会被重新编码
+    pub(crate) async fn respond_308(&mut self, location: &str) -> Result<()> {
+        let location = match self.query() {
+            Some(query) => format!("{}?{}", path::encode(location), query),
+            None => path::encode(location).into_owned(),
+        };
+        let res = Response::redirect(&location, StatusCode::PERMANENT_REDIRECT);
+        self.respond(res).await
+    }
+
+    /// 发送400响应并关闭连接
+    pub(crate) async fn respond_400(&mut self) -> Result<()> {
+        self.keep_alive = false;
+        let res = ResponseBuilder::new(0)
+            .status(StatusCode::BAD_REQUEST)
+            .body(b"Bad Request")
+            .build();
+        self.respond(res).await
+    }
+
+    /// 根据 `Connection` 请求头和协议版本判断是否保持连接,
+    /// HTTP/1.1 默认保持连接, HTTP/1.0 默认关闭
+    pub(crate) fn init_keep_alive(&mut self, remaining_requests: usize) {
+        let mut keep_alive = self.version() != "HTTP/1.0";
+        for token in self.headers().get_list("Connection") {
+            if token.eq_ignore_ascii_case("close") {
+                keep_alive = false;
+            } else if token.eq_ignore_ascii_case("keep-alive") {
+                keep_alive = true;
+            }
+        }
+
+        self.keep_alive = keep_alive && remaining_requests > 1;
+        self.remaining_requests = remaining_requests - 1;
+    }
+
+    /// 响应后是否保持连接,
+    /// 没有回复 `100 Continue` 就发送了响应时无法确定客户端是否还会发送请求体, 需要关闭连接
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive && !self.expect_continue
+    }
+
+    /// 响应后关闭连接, 需要在发送响应之前调用
+    pub fn close_connection(&mut self) {
+        self.keep_alive = false;
+    }
+
+    /// `Keep-Alive` 响应头的值
+    fn keep_alive_params(&self) -> String {
+        format!(
+            "timeout={}, max={}",
+            self.config.keep_alive_timeout.as_secs(),
+            self.remaining_requests
+        )
     }
 }
 
+/// `write_vectored` 可能只写入一部分, 循环直到 `bufs` 全部写完
+async fn write_all_vectored(stream: &mut GlacierStream, bufs: &[&[u8]]) -> Result<()> {
+    let mut bufs: Vec<&[u8]> = bufs.iter().copied().filter(|b| !b.is_empty()).collect();
+    let mut start = 0;
+    while start < bufs.len() {
+        let slices: Vec<IoSlice> = bufs[start..].iter().map(|b| IoSlice::new(b)).collect();
+        let mut len = stream.write_vectored(&slices).await?;
+        if len == 0 {
+            Err(std::io::Error::from(std::io::ErrorKind::WriteZero))?
+        }
+
+        while start < bufs.len() && len >= bufs[start].len() {
+            len -= bufs[start].len();
+            start += 1;
+        }
+        if start < bufs.len() {
+            bufs[start] = &bufs[start][len..];
+        }
+    }
+
+    Ok(())
+}
+
 /* ----------------------------------- RequestLine ----------------------------------- */
 #[derive(Debug)]
 pub struct RequestLine;
@@ -265,19 +1062,30 @@ impl RequestLine {
         // GET /favicon.ico HTTP/1.1\r\n
 
         let request_line = unsafe { buf.get_unchecked(pos[0]..pos[1]) };
-
-        let mut first_space = 0;
-        let mut second_space = 0;
-        for i in 0..request_line.len() {
-            if b' ' == request_line[i] {
-                if first_space == 0 {
-                    first_space = i;
-                } else {
-                    second_space = i;
-                    break;
-                }
-            }
+        if request_line[..request_line.len() - 2]
+            .iter()
+            .any(|b| matches!(b, b'\r' | b'\n' | b'\0'))
+        {
+            Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "CR, LF or NUL in request line",
+            ))?
         }
+        // 方法、路径和版本之后都当作 `&str` 使用, 这里检查一次
+        if std::str::from_utf8(request_line).is_err() {
+            Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "request line is not valid utf8",
+            ))?
+        }
+
+        let mut spaces = request_line
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| **b == b' ')
+            .map(|(i, _)| i);
+        let first_space = spaces.next().unwrap_or(0);
+        let second_space = spaces.next().unwrap_or(0);
         if second_space == 0 {
             match std::str::from_utf8(request_line) {
                 Ok(line) => tracing::debug!(line, "failed parsing request line"),
@@ -286,7 +1094,12 @@ impl RequestLine {
             Err(GlacierError::Option)?
         }
 
-        Ok([0, first_space + 1, second_space + 1, pos[1] - 1])
+        Ok([
+            pos[0],
+            pos[0] + first_space + 1,
+            pos[0] + second_space + 1,
+            pos[1] - 1,
+        ])
     }
 }
 
@@ -298,8 +1111,17 @@ impl RequestHeader {
     pub(crate) fn parse(buf: &BytesMut, line: [usize; 2]) -> Result<[usize; 3]> {
         let header = unsafe { buf.get_unchecked(line[0]..line[1] - 2) };
 
-        for i in 0..header.len() {
-            if b':' == header[i] {
+        // 单独的 `\n` 不是行尾, 留在值里会和接受 `\n` 换行的代理理解不一致
+        if header.iter().any(|b| matches!(b, b'\r' | b'\n' | b'\0')) {
+            Err(GlacierError::not_ok_err(
+                Kind::InRequest,
+                "CR, LF or NUL in request header",
+            ))?
+        }
+
+        // 名字不能为空, 也不能包含空白 (包括以空白开头的折叠行)
+        if let Some(i) = header.iter().position(|b| *b == b':') {
+            if i > 0 && !header[..i].iter().any(|b| b.is_ascii_whitespace()) {
                 return Ok([line[0], line[0] + i, line[1]]);
             }
         }
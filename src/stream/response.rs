@@ -1,24 +1,324 @@
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
+use crate::stream::body::BodyStream;
+use crate::stream::status::StatusCode;
+use crate::stream::{date, mime, path};
+
+/* ----------------------------------- Response ----------------------------------- */
+/// 响应, 发送时才序列化, 之前可以随时修改状态码、响应头和响应体
+/// # Examples
+/// ```
+/// let mut res = ResponseBuilder::new(128)
+///     .content_type(ContentType::Json)
+///     .body(br#"{"ok":true}"#)
+///     .build();
+///
+/// // 中间件可以继续修改
+/// res.status = StatusCode::CREATED;
+/// res.headers.insert("Cache-Control", "no-store");
+/// req.respond(res).await?;
+/// ```
 #[derive(Debug)]
 pub struct Response {
-    pub buf: BytesMut,
+    pub status: StatusCode,
+    pub headers: ResponseHeaders,
+    pub body: Body,
+    /// `chunked` 响应体之后发送的尾部字段
+    pub trailers: Option<Trailers>,
+}
+
+impl Response {
+    /// 没有响应体的响应
+    pub fn new(status: impl Into<StatusCode>) -> Self {
+        Response {
+            status: status.into(),
+            headers: ResponseHeaders::new(),
+            body: Body::Empty,
+            trailers: None,
+        }
+    }
+
+    /// 重定向到 `location`, 可以是绝对地址或者路径, 不能出现在响应头中的字符会被百分号编码.
+    /// `status` 只能是 301、302、303、307、308, 否则使用302
+    /// # Examples
+    /// ```
+    /// let res = Response::redirect("/login?next=/home", StatusCode::SEE_OTHER);
+    /// req.respond(res).await?;
+    /// ```
+    pub fn redirect(location: &str, status: impl Into<StatusCode>) -> Self {
+        let mut status = status.into();
+        if ![301, 302, 303, 307, 308].contains(&status.as_u16()) {
+            tracing::error!(status = status.as_u16(), "invalid redirect status");
+            status = StatusCode::FOUND;
+        }
+
+        let mut res = Response::new(status);
+        res.headers
+            .insert("Location", path::encode_location(location));
+        res
+    }
+
+    /// 状态行和响应头, 以空行结尾
+    pub(crate) fn head(&self) -> BytesMut {
+        let status_line = self.status.status_line();
+        let mut buf = BytesMut::with_capacity(status_line.len() + 32 * self.headers.len() + 2);
+        buf.put_slice(status_line.as_bytes());
+        for (key, value) in self.headers.iter() {
+            buf.put_slice(key.as_bytes());
+            buf.put_slice(b": ");
+            buf.put_slice(value.as_bytes());
+            buf.put_slice(b"\r\n");
+        }
+        buf.put_slice(b"\r\n");
+
+        buf
+    }
+
+    /// 补充 `Date` 和 `Server`, 已经设置的不会覆盖
+    pub(crate) fn fill_date_and_server(&mut self, server: Option<&str>) {
+        if !self.headers.contains("Date") {
+            self.headers.insert("Date", date::now());
+        }
+        if let Some(server) = server {
+            if !self.headers.contains("Server") {
+                self.headers.insert("Server", server);
+            }
+        }
+    }
+
+    /// `1xx`、`204` 和 `304` 不能有响应体
+    pub(crate) fn allows_body(&self) -> bool {
+        !self.status.is_informational()
+            && self.status != StatusCode::NO_CONTENT
+            && self.status != StatusCode::NOT_MODIFIED
+    }
+}
+
+/* ----------------------------------- Body ----------------------------------- */
+/// 响应体
+pub enum Body {
+    Empty,
+    Bytes(Bytes),
+    /// 发送时才打开文件, 不会一次读入内存
+    File(PathBuf),
+    /// 没有设置 `Content-Length` 时使用 `Transfer-Encoding: chunked` 发送
+    Stream(BodyStream<'static>),
+}
+
+impl std::fmt::Debug for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Body::Empty => f.write_str("Empty"),
+            Body::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            Body::File(path) => f.debug_tuple("File").field(path).finish(),
+            Body::Stream(_) => f.write_str("Stream"),
+        }
+    }
+}
+
+/* ----------------------------------- Trailers ----------------------------------- */
+/// 响应的尾部字段, 可以在发送响应体的过程中添加, 例如响应体的校验和.
+/// 只有 `Transfer-Encoding: chunked` 的响应会发送
+/// # Examples
+/// ```
+/// let trailers = Trailers::new();
+/// let writer = trailers.clone();
+/// let rows = rows.map(move |row| {
+///     // ...
+///     writer.insert("X-Rows", count.to_string());
+///     row
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Trailers {
+    fields: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Trailers {
+    pub fn new() -> Self {
+        Trailers::default()
+    }
+
+    /// 设置尾部字段, 替换同名字段
+    pub fn insert(&self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let mut fields = self.fields.lock().unwrap_or_else(|e| e.into_inner());
+        fields.retain(|(key, _)| !key.eq_ignore_ascii_case(&name));
+        fields.push((name, value.into()));
+    }
+
+    /// 取出所有字段, 发送响应体之后调用
+    pub(crate) fn take(&self) -> Vec<(String, String)> {
+        let mut fields = self.fields.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut *fields)
+    }
+}
+
+/* ----------------------------------- ResponseHeaders ----------------------------------- */
+/// 响应头, 保持插入顺序, 名字不区分大小写
+#[derive(Debug, Clone, Default)]
+pub struct ResponseHeaders {
+    headers: Vec<(String, String)>,
+}
+
+impl ResponseHeaders {
+    pub fn new() -> Self {
+        ResponseHeaders {
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// 查找第一个同名响应头
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).next()
+    }
+
+    pub fn get_all<'a, 'n>(&'a self, name: &'n str) -> impl Iterator<Item = &'a str> + 'n
+    where
+        'a: 'n,
+    {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// 设置响应头, 替换所有同名响应头, 位置与第一个同名响应头相同
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+        let first = self
+            .headers
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(&name));
+
+        match first {
+            Some(first) => {
+                let mut i = 0;
+                self.headers.retain(|(key, _)| {
+                    i += 1;
+                    i - 1 == first || !key.eq_ignore_ascii_case(&name)
+                });
+                self.headers[first] = (name, value);
+            }
+            None => self.headers.push((name, value)),
+        }
+    }
+
+    /// 添加响应头, 保留同名响应头, 例如多个 `Set-Cookie`
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.headers.push((name.into(), value.into()));
+    }
+
+    /// 删除所有同名响应头, 返回第一个的值
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let first = self
+            .headers
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(name))?;
+        let (_, value) = self.headers.remove(first);
+        self.headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+
+        Some(value)
+    }
 }
 
+/* ----------------------------------- ResponseBuilder ----------------------------------- */
+/// 常用的响应格式, 其他格式使用 `Custom`
+/// # Examples
+/// ```
+/// ContentType::Css
+/// ContentType::Custom(String::from("application/vnd.api+json"))
+/// ContentType::from_path("public/logo.svg")
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ContentType {
     Plain,
     Html,
     Json,
+    Css,
+    JavaScript,
+    Xml,
+    Csv,
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Svg,
+    Ico,
+    Wasm,
+    Pdf,
+    Woff,
+    Woff2,
+    OctetStream,
+    Custom(String),
 }
 
+impl ContentType {
+    /// 根据文件扩展名判断, 见 [`mime::from_path`]
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Self {
+        ContentType::Custom(mime::from_path(path))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ContentType::Plain => "text/plain; charset=UTF-8",
+            ContentType::Html => "text/html; charset=UTF-8",
+            ContentType::Json => "application/json; charset=UTF-8",
+            ContentType::Css => "text/css; charset=UTF-8",
+            ContentType::JavaScript => "text/javascript; charset=UTF-8",
+            ContentType::Xml => "application/xml",
+            ContentType::Csv => "text/csv; charset=UTF-8",
+            ContentType::Png => "image/png",
+            ContentType::Jpeg => "image/jpeg",
+            ContentType::Gif => "image/gif",
+            ContentType::Webp => "image/webp",
+            ContentType::Svg => "image/svg+xml",
+            ContentType::Ico => "image/x-icon",
+            ContentType::Wasm => "application/wasm",
+            ContentType::Pdf => "application/pdf",
+            ContentType::Woff => "font/woff",
+            ContentType::Woff2 => "font/woff2",
+            ContentType::OctetStream => mime::OCTET_STREAM,
+            ContentType::Custom(value) => value,
+        }
+    }
+}
+
+/// 状态行在发送时才写入, 所以 `status` 可以在任意位置调用
 pub struct ResponseBuilder {
-    buf: BytesMut,
+    res: Response,
 }
 
 impl ResponseBuilder {
     /// 创建一个响应构造器
     /// # Args
-    /// - `capacity` - 响应的预期大小
+    /// - `capacity` - 响应的预期大小, 响应在发送时才序列化, 这个参数已经不再使用
     /// # Exanples
     /// ```
     ///
@@ -31,18 +331,18 @@ impl ResponseBuilder {
     /// req.respond(res).await.unwrap();
     ///
     /// ```
-    pub fn new(capacity: usize) -> Self {
+    pub fn new(_capacity: usize) -> Self {
         ResponseBuilder {
-            buf: BytesMut::with_capacity(capacity),
+            res: Response::new(StatusCode::OK),
         }
     }
 
-    /// 设置响应代码
+    /// 设置响应代码, 默认200, 可以传入 `u16` 或者 [`StatusCode`]
     /// # Examples
     /// ```
     ///
     /// let res = ResponseBuilder::new(128)
-    ///     .status(200)
+    ///     .status(StatusCode::CREATED)
     ///     .header("Connection", "close")
     ///     .content_type(ContentType::Plain)
     ///     .body("Hello, World!")
@@ -50,16 +350,12 @@ impl ResponseBuilder {
     /// req.respond(res).await.unwrap();
     ///
     /// ```
-    pub fn status(mut self, status: u16) -> Self {
-        match status {
-            200 => self.buf.put(&b"HTTP/1.1 200 OK\r\n"[..]),
-            404 => self.buf.put(&b"HTTP/1.1 404 Not Found\r\n"[..]),
-            _ => {}
-        }
+    pub fn status(mut self, status: impl Into<StatusCode>) -> Self {
+        self.res.status = status.into();
         self
     }
 
-    /// 设置响应头
+    /// 添加响应头, 同名响应头会保留, 需要替换时使用 [`ResponseHeaders::insert`]
     /// # Examples
     /// ```
     ///
@@ -73,11 +369,25 @@ impl ResponseBuilder {
     ///
     /// ```
     pub fn header(mut self, key: &str, value: &str) -> Self {
-        self.buf.put(key.as_bytes());
-        self.buf.put(&b": "[..]);
-        self.buf.put(value.as_bytes());
-        self.buf.put(&b"\r\n"[..]);
+        self.res.headers.append(key, value);
+        self
+    }
 
+    /// 设置 `Vary` 响应头, 告诉缓存响应会随哪些请求头变化.
+    /// 使用了 `req.accepts()` 等内容协商方法时, 没有设置 `Vary` 的响应会自动加上
+    /// # Examples
+    /// ```
+    ///
+    /// let res = ResponseBuilder::new(128)
+    ///     .status(200)
+    ///     .vary(&["Accept", "Accept-Encoding"])
+    ///     .body(b"Hello, World!")
+    ///     .build();
+    /// req.respond(res).await.unwrap();
+    ///
+    /// ```
+    pub fn vary(mut self, names: &[&str]) -> Self {
+        self.res.headers.insert("Vary", names.join(", "));
         self
     }
 
@@ -94,12 +404,9 @@ impl ResponseBuilder {
     /// req.respond(res).await.unwrap();
     ///
     /// ```
-    pub fn content_type(self, t: ContentType) -> Self {
-        match t {
-            ContentType::Plain => self.header("Content-Type", "text/plain; charset=UTF-8"),
-            ContentType::Html => self.header("Content-Type", "text/html; charset=UTF-8"),
-            ContentType::Json => self.header("Content-Type", "application/json; charset=UTF-8"),
-        }
+    pub fn content_type(mut self, t: ContentType) -> Self {
+        self.res.headers.insert("Content-Type", t.as_str());
+        self
     }
 
     /// 设置响应体
@@ -116,11 +423,106 @@ impl ResponseBuilder {
     ///
     /// ```
     pub fn body(mut self, body: &[u8]) -> Self {
-        self.buf.put_slice(b"Content-Length: ");
-        self.buf.put(body.len().to_string().as_bytes());
-        self.buf.put_slice(b"\r\n\r\n");
-        self.buf.put_slice(body);
+        self.res.body = Body::Bytes(Bytes::copy_from_slice(body));
+        self
+    }
 
+    /// 将 `value` 序列化为 JSON 作为响应体, 并设置 `Content-Type`,
+    /// 序列化失败时变成 `500 Internal Server Error`
+    /// # Examples
+    /// ```
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     name: String,
+    /// }
+    ///
+    /// let res = ResponseBuilder::new(128).json(&user).build();
+    /// req.respond(res).await?;
+    /// ```
+    pub fn json<T: Serialize + ?Sized>(self, value: &T) -> Self {
+        let json = serde_json::to_vec(value);
+        self.json_body(json)
+    }
+
+    /// 与 [`ResponseBuilder::json`] 相同, 但是带缩进
+    pub fn json_pretty<T: Serialize + ?Sized>(self, value: &T) -> Self {
+        let json = serde_json::to_vec_pretty(value);
+        self.json_body(json)
+    }
+
+    fn json_body(self, json: serde_json::Result<Vec<u8>>) -> Self {
+        match json {
+            Ok(json) => self.content_type(ContentType::Json).body(&json),
+            Err(e) => {
+                tracing::error!(e = e.to_string(), "failed to serialize json response");
+                let mut builder = self.status(StatusCode::INTERNAL_SERVER_ERROR);
+                builder.res.headers = ResponseHeaders::new();
+                builder
+                    .content_type(ContentType::Plain)
+                    .body(b"Internal Server Error")
+            }
+        }
+    }
+
+    /// 使用文件作为响应体, 发送时从磁盘读取,
+    /// 没有设置 `Content-Type` 时根据扩展名判断
+    /// # Examples
+    /// ```
+    /// let res = ResponseBuilder::new(128)
+    ///     .header("Content-Type", "video/mp4")
+    ///     .file("videos/intro.mp4")
+    ///     .build();
+    /// req.respond(res).await?;
+    /// ```
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.res.body = Body::File(path.into());
+        self
+    }
+
+    /// 使用流作为响应体, 以 `Transfer-Encoding: chunked` 发送
+    /// # Examples
+    /// ```
+    /// let chunks = futures::stream::iter(vec![Ok(Bytes::from("a")), Ok(Bytes::from("b"))]);
+    /// let res = ResponseBuilder::new(128).stream(Box::pin(chunks)).build();
+    /// req.respond(res).await?;
+    /// ```
+    pub fn stream(mut self, stream: BodyStream<'static>) -> Self {
+        self.res.body = Body::Stream(stream);
+        self
+    }
+
+    /// 以 `Transfer-Encoding: chunked` 逐块发送响应体, 不需要提前知道长度.
+    /// 每一块写入连接之后才会读取下一块, 客户端读得慢时不会在内存中堆积
+    /// # Examples
+    /// ```
+    /// let lines = futures::stream::iter(rows).map(|row| Bytes::from(row.to_csv()));
+    /// let res = ResponseBuilder::new(128)
+    ///     .header("Content-Type", "text/csv")
+    ///     .body_stream(lines)
+    ///     .build();
+    /// req.respond(res).await?;
+    /// ```
+    pub fn body_stream<S>(self, stream: S) -> Self
+    where
+        S: Stream<Item = Bytes> + Send + 'static,
+    {
+        self.stream(Box::pin(stream.map(Ok)))
+    }
+
+    /// 在 `chunked` 响应体之后发送尾部字段, 同时设置 `Trailer` 响应头声明字段名
+    /// # Examples
+    /// ```
+    /// let trailers = Trailers::new();
+    /// let res = ResponseBuilder::new(128)
+    ///     .body_stream(chunks)
+    ///     .trailers(&["X-Checksum"], trailers.clone())
+    ///     .build();
+    /// ```
+    pub fn trailers(mut self, names: &[&str], trailers: Trailers) -> Self {
+        if !names.is_empty() {
+            self.res.headers.insert("Trailer", names.join(", "));
+        }
+        self.res.trailers = Some(trailers);
         self
     }
 
@@ -138,6 +540,62 @@ impl ResponseBuilder {
     ///
     /// ```
     pub fn build(self) -> Response {
-        Response { buf: self.buf }
+        self.res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_line_comes_first() {
+        let res = ResponseBuilder::new(128)
+            .header("X-Id", "1")
+            .content_type(ContentType::Plain)
+            .header("x-id", "2")
+            .status(StatusCode::CREATED)
+            .body(b"ok")
+            .build();
+        assert_eq!(
+            &res.head()[..],
+            b"HTTP/1.1 201 Created\r\nX-Id: 1\r\nContent-Type: text/plain; charset=UTF-8\r\nx-id: 2\r\n\r\n"
+        );
+
+        let res = ResponseBuilder::new(128).status(599).build();
+        assert_eq!(&res.head()[..], b"HTTP/1.1 599 \r\n\r\n");
+        assert_eq!(StatusCode::from(42), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn insert_replaces_headers() {
+        let mut res = ResponseBuilder::new(128)
+            .header("X-Id", "1")
+            .content_type(ContentType::Plain)
+            .header("x-id", "2")
+            .build();
+        res.headers.insert("X-ID", "3");
+        res.headers.insert("content-type", "text/html");
+        assert_eq!(res.headers.get_all("X-Id").collect::<Vec<_>>(), ["3"]);
+        assert_eq!(
+            res.headers.remove("Content-Type").as_deref(),
+            Some("text/html")
+        );
+        assert_eq!(res.headers.len(), 1);
+    }
+
+    #[test]
+    fn json_body() {
+        let res = ResponseBuilder::new(0).json(&[1, 2]).build();
+        assert_eq!(
+            res.headers.get("content-type"),
+            Some("application/json; charset=UTF-8")
+        );
+        assert!(matches!(res.body, Body::Bytes(ref b) if &b[..] == b"[1,2]"));
+
+        // map 的 key 不是字符串时无法序列化
+        let invalid = std::collections::HashMap::from([((1, 2), 3)]);
+        let res = ResponseBuilder::new(0).json(&invalid).build();
+        assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
     }
 }
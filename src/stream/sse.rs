@@ -0,0 +1,189 @@
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::prelude::{GlacierError, Kind, Result};
+
+/* ----------------------------------- Event ----------------------------------- */
+/// 一条服务器推送事件 (Server-Sent Events)
+/// # Examples
+/// ```
+/// let event = Event::new()
+///     .event("price")
+///     .id("42")
+///     .data("{\"btc\":1}");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Event {
+    event: Option<String>,
+    data: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+    comment: Option<String>,
+}
+
+impl Event {
+    pub fn new() -> Self {
+        Event::default()
+    }
+
+    /// 事件类型, 客户端用 `addEventListener(event, ...)` 接收, 默认 `message`
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// 事件数据, 多行数据会拆成多个 `data:` 字段
+    pub fn data(mut self, data: impl Into<String>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// 事件 id, 客户端重连时通过 `Last-Event-ID` 请求头带回
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// 客户端断开之后重连的等待时间
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// 注释, 客户端会忽略
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// 序列化为 `text/event-stream` 格式, 字段中的换行会被去掉或拆分
+    pub(crate) fn to_bytes(&self) -> Bytes {
+        let mut buf = String::new();
+        let mut push_lines = |field: &str, value: &str| {
+            for line in value.split('\n') {
+                buf.push_str(field);
+                buf.push_str(line.strip_suffix('\r').unwrap_or(line));
+                buf.push('\n');
+            }
+        };
+
+        if let Some(comment) = &self.comment {
+            push_lines(": ", comment);
+        }
+        if let Some(event) = &self.event {
+            push_lines("event: ", single_line(event));
+        }
+        if let Some(id) = &self.id {
+            push_lines("id: ", single_line(id));
+        }
+        if let Some(retry) = self.retry {
+            push_lines("retry: ", &retry.as_millis().to_string());
+        }
+        if let Some(data) = &self.data {
+            push_lines("data: ", data);
+        }
+        buf.push('\n');
+
+        Bytes::from(buf)
+    }
+}
+
+/// `event` 和 `id` 只能有一行, 只保留第一行
+fn single_line(value: &str) -> &str {
+    value.split(['\r', '\n']).next().unwrap_or_default()
+}
+
+/* ----------------------------------- channel ----------------------------------- */
+/// 创建推送事件的通道, `SseSender` 交给产生事件的任务,
+/// `SseReceiver` 交给 [`OneRequest::respond_sse`](crate::prelude::OneRequest::respond_sse)
+/// # Args
+/// - `buffer` - 等待发送的事件的最大数量, 满了之后 `send` 会等待
+///
+/// # Examples
+/// ```
+/// let (tx, events) = sse::channel(16);
+/// tokio::spawn(async move {
+///     let mut n = 0;
+///     while tx.send(Event::new().data(n.to_string())).await.is_ok() {
+///         n += 1;
+///         tokio::time::sleep(Duration::from_secs(1)).await;
+///     }
+/// });
+/// req.respond_sse(events).await?;
+/// ```
+pub fn channel(buffer: usize) -> (SseSender, SseReceiver) {
+    let (tx, rx) = mpsc::channel(buffer.max(1));
+    (SseSender { tx }, SseReceiver::new(rx))
+}
+
+/// 推送事件, 可以克隆给多个任务
+#[derive(Debug, Clone)]
+pub struct SseSender {
+    tx: mpsc::Sender<Event>,
+}
+
+impl SseSender {
+    /// 发送一个事件, 客户端已经断开时返回错误
+    pub async fn send(&self, event: Event) -> Result<()> {
+        self.tx
+            .send(event)
+            .await
+            .map_err(|_| GlacierError::OkErr(Kind::EofErr))
+    }
+
+    /// 不等待, 队列满了或者客户端已经断开时返回错误
+    pub fn try_send(&self, event: Event) -> Result<()> {
+        self.tx.try_send(event).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => {
+                GlacierError::not_ok_err(Kind::InServer, "sse queue is full")
+            }
+            mpsc::error::TrySendError::Closed(_) => GlacierError::OkErr(Kind::EofErr),
+        })
+    }
+
+    /// 客户端是否已经断开
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+#[derive(Debug)]
+pub struct SseReceiver {
+    pub(crate) rx: mpsc::Receiver<Event>,
+    pub(crate) ping_interval: Duration,
+}
+
+impl SseReceiver {
+    pub(crate) fn new(rx: mpsc::Receiver<Event>) -> Self {
+        SseReceiver {
+            rx,
+            ping_interval: Duration::from_secs(15),
+        }
+    }
+
+    /// 没有事件时发送注释保持连接的间隔, 默认15秒, 防止代理因为空闲关闭连接
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_format() {
+        let event = Event::new()
+            .event("update\nx")
+            .id("7")
+            .retry(Duration::from_secs(3))
+            .data("line1\r\nline2");
+        assert_eq!(
+            &event.to_bytes()[..],
+            b"event: update\nid: 7\nretry: 3000\ndata: line1\ndata: line2\n\n"
+        );
+        assert_eq!(&Event::new().comment("ping").to_bytes()[..], b": ping\n\n");
+    }
+}
@@ -0,0 +1,142 @@
+use notify::{
+    Config, Event as FsEvent, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{mpsc, LazyLock, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::prelude::{Event, GlacierError, Kind, Result};
+use crate::stream::{
+    file_cache,
+    mount::{Mount, LIVE_RELOAD},
+};
+
+/// 每个挂载点的监听器, 被替换或删除时停止监听
+static WATCHERS: LazyLock<Mutex<HashMap<String, Box<dyn Watcher + Send>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 编辑器保存文件时会连续产生多个事件, 合并这段时间内的事件
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// 开始监听挂载目录, 没有开启 `watch` 时停止之前相同前缀的监听
+pub(crate) fn watch(mount: &Mount) -> Result<()> {
+    let mut watchers = WATCHERS.lock().unwrap_or_else(|e| e.into_inner());
+    watchers.remove(&mount.prefix);
+    if !mount.options.watch {
+        return Ok(());
+    }
+
+    let watch_err = |e: notify::Error| {
+        GlacierError::not_ok_err(Kind::IOErr, format!("watch {}: {}", mount.root, e))
+    };
+    let root = fs::canonicalize(&mount.root)?;
+    let (tx, rx) = mpsc::channel();
+
+    // 优先使用系统的通知机制, Linux 上是 inotify, 不支持时轮询
+    let mut watcher: Box<dyn Watcher + Send> =
+        match RecommendedWatcher::new(tx.clone(), Config::default()) {
+            Ok(watcher) => Box::new(watcher),
+            Err(e) => {
+                tracing::warn!(root = mount.root, %e, "fall back to polling");
+                let config = Config::default().with_poll_interval(Duration::from_secs(1));
+                Box::new(PollWatcher::new(tx, config).map_err(watch_err)?)
+            }
+        };
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(watch_err)?;
+    watchers.insert(mount.prefix.clone(), watcher);
+
+    tracing::info!(
+        prefix = mount.prefix,
+        root = mount.root,
+        "watching static resources"
+    );
+
+    // 监听器被丢弃后通道关闭, 线程随之结束
+    let mount = mount.clone();
+    thread::spawn(move || {
+        while let Ok(event) = rx.recv() {
+            let mut changed = on_event(&mount, &root, event);
+            while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+                changed |= on_event(&mount, &root, event);
+            }
+
+            if changed && mount.options.live_reload {
+                LIVE_RELOAD.publish("reload", Event::new().event("reload").data(&mount.prefix));
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// 处理一个事件, 返回是否有文件改变
+fn on_event(mount: &Mount, root: &Path, event: notify::Result<FsEvent>) -> bool {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            tracing::warn!(root = mount.root, %e, "watch error");
+            return false;
+        }
+    };
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    let mut changed = false;
+    for path in &event.paths {
+        let Some(rel) = relative(root, path) else {
+            continue;
+        };
+        if mount.is_hidden(&rel) {
+            continue;
+        }
+
+        refresh(mount, &mount.file_path(&rel));
+        changed = true;
+    }
+
+    changed
+}
+
+/// 监听的目录中的路径转换为相对路径, 使用 `/` 分隔
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let parts: Option<Vec<&str>> = rel.components().map(|c| c.as_os_str().to_str()).collect();
+    Some(parts?.join("/"))
+}
+
+/// 更新缓存中的文件, ETag 随之重新计算. 文件或目录被删除时删除对应的缓存,
+/// 没有缓存的文件在下次请求时读取
+fn refresh(mount: &Mount, file_path: &str) {
+    match fs::metadata(file_path) {
+        Ok(metadata) if metadata.is_file() => {
+            if !file_cache::lock().contains(file_path) {
+                return;
+            }
+            let buf = match metadata.len() <= mount.options.cache_threshold {
+                true => fs::read(file_path).ok(),
+                false => None,
+            };
+            match buf {
+                Some(buf) => {
+                    let etag = file_cache::etag(&metadata);
+                    file_cache::lock().insert(file_path, buf.into(), etag);
+                }
+                None => file_cache::lock().remove(file_path),
+            }
+        }
+        Ok(_) => {}
+        Err(_) => {
+            let mut cache = file_cache::lock();
+            cache.remove(file_path);
+            cache.remove_dir(&format!("{}/", file_path));
+        }
+    }
+}